@@ -1,10 +1,11 @@
 //! Availability evaluation tools.
 
-use crate::manifest::Manifest;
+use crate::{manifest::Manifest, types::TargetComponents};
 use chrono::NaiveDate;
 use std::{
     borrow::Borrow,
     collections::{HashMap, HashSet},
+    hash::Hash,
 };
 
 type PackageName = String;
@@ -20,6 +21,7 @@ pub struct AvailabilityData {
 
 /// A single row in an availability table.
 #[derive(Debug, serde::Serialize)]
+#[allow(clippy::manual_non_exhaustive)]
 pub struct AvailabilityRow<'a> {
     /// Name of the package.
     pub package_name: &'a str,
@@ -27,10 +29,109 @@ pub struct AvailabilityRow<'a> {
     pub availability_list: Vec<bool>,
     /// Date when the component has been available for the last time.
     pub last_available: Option<NaiveDate>,
+    /// Availability statistics over the row's dates, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<AvailabilityStats>,
     /// A hidden field to improve compatibility.
     _hidden: (),
 }
 
+/// Availability statistics of a package on a target over a window of dates.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AvailabilityStats {
+    /// Number of dates in the window.
+    pub days: usize,
+    /// Number of dates when the package was available.
+    pub available_days: usize,
+    /// Fraction of dates when the package was available, from 0 to 1.
+    pub ratio: f64,
+    /// The longest streak of consecutive dates when the package was missing.
+    pub longest_outage: usize,
+    /// Mean number of dates the package stayed available before breaking, i.e. before going
+    /// missing. `None` if it has never broken within the window.
+    pub mean_time_between_breakages: Option<f64>,
+    /// Number of times the package went from available to missing or vice versa.
+    pub flaps: usize,
+}
+
+impl<'a> AvailabilityRow<'a> {
+    /// Makes a row of a package given all the dates when it was available.
+    pub(crate) fn from_available_dates<I>(
+        pkg: &'a str,
+        available_dates: &HashSet<NaiveDate>,
+        dates: I,
+    ) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<NaiveDate>,
+    {
+        AvailabilityRow {
+            package_name: pkg,
+            availability_list: dates
+                .into_iter()
+                .map(|date| available_dates.contains(date.borrow()))
+                .collect(),
+            last_available: available_dates.iter().max().copied(),
+            stats: None,
+            _hidden: (),
+        }
+    }
+}
+
+impl AvailabilityStats {
+    /// Evaluates statistics over the given (possibly unsorted) dates, given all the dates when a
+    /// package was available.
+    pub(crate) fn from_available_dates<I>(available_dates: &HashSet<NaiveDate>, dates: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<NaiveDate>,
+    {
+        let mut dates: Vec<NaiveDate> = dates.into_iter().map(|date| *date.borrow()).collect();
+        dates.sort_unstable();
+        AvailabilityStats::new(dates.iter().map(|date| available_dates.contains(date)))
+    }
+
+    /// Evaluates statistics over a list of availabilities sorted in a chronological order.
+    fn new(availability: impl IntoIterator<Item = bool>) -> Self {
+        let mut stats = AvailabilityStats {
+            days: 0,
+            available_days: 0,
+            ratio: 0.,
+            longest_outage: 0,
+            mean_time_between_breakages: None,
+            flaps: 0,
+        };
+        let mut breakages = 0;
+        let mut outage = 0;
+        let mut previous = None;
+        for available in availability {
+            stats.days += 1;
+            if available {
+                stats.available_days += 1;
+                outage = 0;
+            } else {
+                outage += 1;
+                stats.longest_outage = stats.longest_outage.max(outage);
+            }
+            if previous == Some(!available) {
+                stats.flaps += 1;
+                if !available {
+                    breakages += 1;
+                }
+            }
+            previous = Some(available);
+        }
+        if stats.days != 0 {
+            stats.ratio = stats.available_days as f64 / stats.days as f64;
+        }
+        if breakages != 0 {
+            stats.mean_time_between_breakages =
+                Some(stats.available_days as f64 / breakages as f64);
+        }
+        stats
+    }
+}
+
 impl AvailabilityData {
     /// Adds an availability data from a given [`Manifest`].
     pub fn add_manifest(&mut self, manifest: Manifest) {
@@ -64,6 +165,20 @@ impl AvailabilityData {
             .for_each(|manifest| self.add_manifest(manifest));
     }
 
+    /// Adds [`Manifest`]s one by one from a fallible iterator, e.g. from
+    /// [`Downloader::iter_last_manifests`](crate::Downloader::iter_last_manifests), so every
+    /// manifest is dropped right after it's been added.
+    ///
+    /// Stops at the first error. The manifests added before the error are kept.
+    pub fn try_add_manifests<E>(
+        &mut self,
+        manifests: impl IntoIterator<Item = Result<Manifest, E>>,
+    ) -> Result<(), E> {
+        manifests
+            .into_iter()
+            .try_for_each(|manifest| manifest.map(|manifest| self.add_manifest(manifest)))
+    }
+
     /// Gets a list of targets that have been extracted from manifest files except for the '*'
     /// target.
     pub fn get_available_targets(&self) -> HashSet<&'_ str> {
@@ -74,11 +189,55 @@ impl AvailabilityData {
             .collect()
     }
 
+    /// Gets a list of targets (except for the '*' target) whose components satisfy a given
+    /// predicate.
+    pub fn get_targets_by<F>(&self, mut predicate: F) -> HashSet<&'_ str>
+    where
+        F: FnMut(&TargetComponents<'_>) -> bool,
+    {
+        self.get_available_targets()
+            .into_iter()
+            .filter(|target| predicate(&TargetComponents::parse(target)))
+            .collect()
+    }
+
+    /// Gets a list of targets for a given architecture, e.g. `aarch64`.
+    pub fn get_targets_by_arch(&self, arch: &str) -> HashSet<&'_ str> {
+        self.get_targets_by(|components| components.arch == arch)
+    }
+
+    /// Gets a list of targets for a given operating system, e.g. `windows`.
+    pub fn get_targets_by_os(&self, os: &str) -> HashSet<&'_ str> {
+        self.get_targets_by(|components| components.os == Some(os))
+    }
+
+    /// Groups targets (except for the '*' target) by a key derived from their components, e.g.
+    /// `|components| components.arch`.
+    ///
+    /// Targets in every group are sorted by name.
+    pub fn group_targets_by<'a, K, F>(&'a self, mut key: F) -> HashMap<K, Vec<&'a str>>
+    where
+        K: Hash + Eq,
+        F: FnMut(TargetComponents<'a>) -> K,
+    {
+        let mut groups: HashMap<K, Vec<&'a str>> = HashMap::new();
+        for target in self.get_available_targets() {
+            groups
+                .entry(key(TargetComponents::parse(target)))
+                .or_default()
+                .push(target);
+        }
+        groups
+            .values_mut()
+            .for_each(|targets| targets.sort_unstable());
+        groups
+    }
+
     /// Returns all available packages throughout all the targets and all the times.
-    pub fn get_available_packages<'a>(&'a self) -> HashSet<&'a str> {
+    pub fn get_available_packages(&self) -> HashSet<&'_ str> {
         self.data
-            .iter()
-            .flat_map(|(_, per_target)| per_target.keys())
+            .values()
+            .flat_map(|per_target| per_target.keys())
             .map(AsRef::as_ref)
             .collect()
     }
@@ -97,20 +256,33 @@ impl AvailabilityData {
         I: IntoIterator,
         I::Item: Borrow<NaiveDate>,
     {
-        if self.data.get(target).and_then(|t| t.get(pkg)).is_none() {
-            return None;
-        }
-        let available_dates = self.available_dates(target, pkg);
-        let availability_list = dates
-            .into_iter()
-            .map(|date| available_dates.contains(date.borrow()))
-            .collect();
-        Some(AvailabilityRow {
-            package_name: pkg,
-            availability_list,
-            last_available: available_dates.into_iter().max(),
-            _hidden: (),
-        })
+        self.data.get(target).and_then(|t| t.get(pkg))?;
+        Some(AvailabilityRow::from_available_dates(
+            pkg,
+            &self.available_dates(target, pkg),
+            dates,
+        ))
+    }
+
+    /// Evaluates availability statistics of a given package on a given target over the given dates.
+    /// The dates don't have to be sorted.
+    ///
+    /// Availability is checked against the specified target and against the `*` target.
+    pub fn get_availability_stats<I>(
+        &self,
+        target: &str,
+        pkg: &str,
+        dates: I,
+    ) -> Option<AvailabilityStats>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<NaiveDate>,
+    {
+        self.data.get(target).and_then(|t| t.get(pkg))?;
+        Some(AvailabilityStats::from_available_dates(
+            &self.available_dates(target, pkg),
+            dates,
+        ))
     }
 
     /// Retrieves a set of all the dates when a given package was available on a given target.
@@ -158,19 +330,27 @@ available = true
         assert!(all_targets.contains("lol"));
 
         let package_exists = availability
-            .get_availability_row("*", "rust-src", vec![NaiveDate::from_ymd(2018, 9, 3)])
+            .get_availability_row(
+                "*",
+                "rust-src",
+                vec![NaiveDate::from_ymd_opt(2018, 9, 3).unwrap()],
+            )
             .unwrap();
         assert_eq!("rust-src", package_exists.package_name);
         assert_eq!(vec!(true), package_exists.availability_list);
         let package_exists = availability.get_availability_row(
             "lol",
             "rust-src",
-            vec![NaiveDate::from_ymd(2018, 9, 3)],
+            vec![NaiveDate::from_ymd_opt(2018, 9, 3).unwrap()],
         );
         // rust-src is not present in lol target
         assert!(package_exists.is_none());
         let package_exists = availability
-            .get_availability_row("lol", "ahaha", vec![NaiveDate::from_ymd(2018, 9, 3)])
+            .get_availability_row(
+                "lol",
+                "ahaha",
+                vec![NaiveDate::from_ymd_opt(2018, 9, 3).unwrap()],
+            )
             .unwrap();
         assert_eq!("ahaha", package_exists.package_name);
         assert_eq!(vec!(true), package_exists.availability_list);
@@ -191,4 +371,88 @@ to = "ahaha"
         assert_eq!(1, all_packages.len());
         assert!(all_packages.contains("kek"));
     }
+
+    #[test]
+    fn stats() {
+        let stats = AvailabilityStats::new(vec![]);
+        assert_eq!(0, stats.days);
+        assert_eq!(0., stats.ratio);
+        assert_eq!(None, stats.mean_time_between_breakages);
+
+        let stats = AvailabilityStats::new(vec![true, true, false, false, true, false, true, true]);
+        assert_eq!(
+            AvailabilityStats {
+                days: 8,
+                available_days: 5,
+                ratio: 5. / 8.,
+                longest_outage: 2,
+                mean_time_between_breakages: Some(2.5),
+                flaps: 4,
+            },
+            stats
+        );
+    }
+
+    #[test]
+    fn stats_by_dates() {
+        let manifests = [
+            r#"date = "2018-09-01"
+[pkg.ahaha.target.lol]
+available = true
+"#,
+            r#"date = "2018-09-03"
+[pkg.ahaha.target.lol]
+available = true
+"#,
+        ];
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifests(manifests.iter().map(|data| toml::from_str(data).unwrap()));
+        let dates = (1..=3)
+            .rev()
+            .map(|day| NaiveDate::from_ymd_opt(2018, 9, day).unwrap());
+        let stats = availability
+            .get_availability_stats("lol", "ahaha", dates)
+            .unwrap();
+        assert_eq!(2, stats.available_days);
+        assert_eq!(1, stats.longest_outage);
+        assert_eq!(Some(2.), stats.mean_time_between_breakages);
+        assert_eq!(2, stats.flaps);
+        assert!(availability
+            .get_availability_stats("lol", "kek", Vec::<NaiveDate>::new())
+            .is_none());
+    }
+
+    #[test]
+    fn group_targets() {
+        let data = r#"date = "2018-09-03"
+[pkg.rust-src.target."*"]
+available = true
+[pkg.rust-std.target.aarch64-unknown-linux-gnu]
+available = true
+[pkg.rust-std.target.aarch64-pc-windows-msvc]
+available = true
+[pkg.rust-std.target.x86_64-pc-windows-gnu]
+available = true
+"#;
+        let parsed_manifest: Manifest = toml::from_str(data).unwrap();
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(parsed_manifest);
+
+        let aarch64 = availability.get_targets_by_arch("aarch64");
+        assert_eq!(2, aarch64.len());
+        assert!(aarch64.contains("aarch64-unknown-linux-gnu"));
+        assert!(aarch64.contains("aarch64-pc-windows-msvc"));
+        let windows = availability.get_targets_by_os("windows");
+        assert_eq!(2, windows.len());
+        assert!(windows.contains("aarch64-pc-windows-msvc"));
+        assert!(windows.contains("x86_64-pc-windows-gnu"));
+
+        let by_os = availability.group_targets_by(|components| components.os);
+        assert_eq!(2, by_os.len());
+        assert_eq!(
+            vec!["aarch64-pc-windows-msvc", "x86_64-pc-windows-gnu"],
+            by_os[&Some("windows")]
+        );
+        assert_eq!(vec!["aarch64-unknown-linux-gnu"], by_os[&Some("linux")]);
+    }
 }
@@ -1,15 +1,175 @@
 //! Cache downloaded manifests.
 
 use crate::{manifest::Manifest, Error};
-use chrono::NaiveDate;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+#[cfg(feature = "gzip")]
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "gzip")]
+use std::io::Read;
 use std::{
-    fs,
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
     path::{Path, PathBuf},
+    process,
 };
 
+/// Compression of the cached manifests.
+///
+/// Regardless of the setting, the cache reads manifests stored with any of the enabled compressions,
+/// so it can be changed at any time.
+///
+/// Gzip and Zstandard are only available with the `gzip` and `zstd` features respectively.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum Compression {
+    /// Plain TOML files.
+    #[default]
+    None,
+    /// Gzip-compressed files.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Zstandard-compressed files.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    const ALL: &'static [Compression] = &[
+        Compression::None,
+        #[cfg(feature = "gzip")]
+        Compression::Gzip,
+        #[cfg(feature = "zstd")]
+        Compression::Zstd,
+    ];
+
+    /// A file extension of a manifest stored with the compression.
+    fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "toml",
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "toml.gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "toml.zst",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::encode_all(data, 19),
+        }
+    }
+
+    fn decompress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut decoded = Vec::new();
+                GzDecoder::new(data).read_to_end(&mut decoded)?;
+                Ok(decoded)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::decode_all(data),
+        }
+    }
+}
+
+/// A policy that defines which cached manifests should be kept.
+///
+/// All the limits are applied at once, so a manifest is kept only if it satisfies each of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Keep manifests only for this many most recent days.
+    #[serde(default)]
+    pub keep_days: Option<usize>,
+    /// Maximum total size of the cache in bytes. The oldest manifests are evicted first.
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    /// Keep manifests only for days that are not older than this many days from now.
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+}
+
+impl RetentionPolicy {
+    /// Checks whether the policy has no limits at all, i.e. it keeps everything.
+    pub fn is_empty(&self) -> bool {
+        self.keep_days.is_none() && self.max_size.is_none() && self.max_age_days.is_none()
+    }
+}
+
+/// Cached data for a single day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// A day the data is cached for.
+    pub date: NaiveDate,
+    /// Files that hold the data.
+    pub paths: Vec<PathBuf>,
+    /// Total size of the files in bytes.
+    pub size: u64,
+}
+
+/// A problem found in a cached entry.
+#[derive(Debug, thiserror::Error)]
+pub enum EntryProblem {
+    /// The manifest can't be read, decompressed or parsed, e.g. because it's truncated.
+    #[error("corrupt manifest: {0}")]
+    Corrupt(#[source] Error),
+    /// The manifest is for another date than its file name says.
+    #[error("the manifest is for {0}")]
+    DateMismatch(NaiveDate),
+    /// There are files for the day, but none of them is a manifest.
+    #[error("no manifest")]
+    NoManifest,
+    /// The metadata of the manifest can't be read or parsed.
+    #[error("corrupt metadata: {0}")]
+    CorruptMetadata(#[source] Error),
+}
+
+/// Metadata of a cached manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryMetadata {
+    /// A URL the manifest has been downloaded from.
+    pub source_url: String,
+    /// When the manifest has been downloaded.
+    pub fetched_at: DateTime<Utc>,
+    /// The `ETag` header of the response, if any.
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// The `Last-Modified` header of the response, if any.
+    #[serde(default)]
+    pub last_modified: Option<String>,
+}
+
+impl EntryMetadata {
+    /// Initializes metadata of a manifest that has just been downloaded from a given URL.
+    pub fn new(source_url: impl Into<String>) -> Self {
+        EntryMetadata {
+            source_url: source_url.into(),
+            fetched_at: Utc::now(),
+            etag: None,
+            last_modified: None,
+        }
+    }
+}
+
 /// A cache that stores manifests on a file system.
+///
+/// Manifests are stored exactly as they were downloaded (optionally compressed) and parsed on
+/// read, so newer versions of the library can extract more data from the same cache.
 pub struct FsCache {
     storage_path: Option<PathBuf>,
+    compression: Compression,
 }
 
 impl FsCache {
@@ -24,47 +184,618 @@ impl FsCache {
         }
         Ok(FsCache {
             storage_path: Some(path.into()),
+            compression: Compression::None,
         })
     }
 
     /// Initializes a no-op cache.
     pub fn noop() -> Self {
-        FsCache { storage_path: None }
+        FsCache {
+            storage_path: None,
+            compression: Compression::None,
+        }
     }
 
-    fn make_file_name(&self, day: NaiveDate) -> PathBuf {
+    /// Sets a compression of newly stored manifests. By default manifests are stored uncompressed.
+    pub fn set_compression(self, compression: Compression) -> Self {
+        FsCache {
+            storage_path: self.storage_path,
+            compression,
+        }
+    }
+
+    fn make_metadata_file_name(&self, stem: &str) -> PathBuf {
         self.storage_path
             .as_ref()
             .unwrap()
-            .join(day.format("%Y-%m-%d.toml").to_string())
+            .join(format!("{}.meta.toml", stem))
+    }
+
+    fn make_file_name(&self, stem: &str, compression: Compression) -> PathBuf {
+        self.storage_path
+            .as_ref()
+            .unwrap()
+            .join(format!("{}.{}", stem, compression.extension()))
     }
 }
 
 impl FsCache {
+    /// Loads a cached manifest for a given day, parsing the originally downloaded file.
+    ///
+    /// Returns `Ok(None)` if there is no cached manifest for the day.
+    pub fn load(&self, day: NaiveDate) -> Result<Option<Manifest>, Error> {
+        self.load_file(&day_stem(day))
+    }
+
+    /// Loads metadata of a cached manifest for a given day.
+    ///
+    /// Returns `Ok(None)` if there is no metadata for the day, which is the case for manifests
+    /// cached by older versions of the library.
+    pub fn metadata(&self, day: NaiveDate) -> Result<Option<EntryMetadata>, Error> {
+        self.load_metadata(&day_stem(day))
+    }
+
+    /// Checks whether a manifest for a given day is cached, without loading it.
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        self.storage_path.is_some()
+            && Compression::ALL
+                .iter()
+                .any(|&compression| self.make_file_name(&day_stem(day), compression).exists())
+    }
+
     pub(crate) fn get(&self, day: NaiveDate) -> Option<Manifest> {
+        self.load(day)
+            .map_err(|e| log::warn!("Can't load manifest: {}", e))
+            .ok()
+            .flatten()
+    }
+
+    /// Stores a downloaded manifest as is, along with its metadata.
+    pub(crate) fn store(&self, day: NaiveDate, data: &[u8], metadata: &EntryMetadata) {
         if self.storage_path.is_none() {
-            return None;
+            return;
         }
 
-        let file_name = self.make_file_name(day);
-        if !file_name.exists() {
-            log::debug!("File {:?} doesn't exist", file_name);
-            return None;
+        match self.save(&day_stem(day), data, metadata) {
+            Ok(_) => log::debug!("Manifest for {} stored", day),
+            Err(e) => log::warn!("Can't save a manifest to the disk: {}", e),
         }
-        Manifest::load_from_fs(&file_name)
-            .map_err(|e| log::warn!("Can't load manifest: {}", e))
+    }
+
+    /// Gets the last downloaded latest manifest along with its metadata.
+    pub(crate) fn get_latest(&self) -> Option<(Manifest, EntryMetadata)> {
+        let load = || -> Result<_, Error> {
+            Ok(self
+                .load_file(LATEST_STEM)?
+                .zip(self.load_metadata(LATEST_STEM)?))
+        };
+        load()
+            .map_err(|e| log::warn!("Can't load the latest manifest: {}", e))
             .ok()
+            .flatten()
     }
 
-    pub(crate) fn store(&self, manifest: &Manifest) {
+    /// Stores the latest manifest as is, along with its metadata.
+    pub(crate) fn store_latest(&self, data: &[u8], metadata: &EntryMetadata) {
         if self.storage_path.is_none() {
             return;
         }
 
-        let file_name = self.make_file_name(manifest.date);
-        match manifest.save_to_file(&file_name) {
-            Ok(_) => log::debug!("Manifest stored at {:?}", file_name),
-            Err(e) => log::warn!("Can't save a manifest to the disk: {}", e),
+        match self.save(LATEST_STEM, data, metadata) {
+            Ok(_) => log::debug!("The latest manifest stored"),
+            Err(e) => log::warn!("Can't save the latest manifest to the disk: {}", e),
         }
     }
+
+    fn load_file(&self, stem: &str) -> Result<Option<Manifest>, Error> {
+        if self.storage_path.is_none() {
+            return Ok(None);
+        }
+
+        // The configured compression goes first as the most probable one.
+        let compressions = Some(self.compression).into_iter().chain(
+            Compression::ALL
+                .iter()
+                .copied()
+                .filter(|&c| c != self.compression),
+        );
+        for compression in compressions {
+            let file_name = self.make_file_name(stem, compression);
+            let data = match read_file(&file_name)? {
+                Some(data) => data,
+                None => continue,
+            };
+            let data = compression
+                .decompress(&data)
+                .map_err(|e| Error::Io(e, format!("decompressing {:?}", file_name)))?;
+            return toml::from_slice(&data)
+                .map(Some)
+                .map_err(|e| Error::TomlDe(e, format!("{:?}", file_name)));
+        }
+        Ok(None)
+    }
+
+    fn load_metadata(&self, stem: &str) -> Result<Option<EntryMetadata>, Error> {
+        if self.storage_path.is_none() {
+            return Ok(None);
+        }
+
+        let file_name = self.make_metadata_file_name(stem);
+        let data = match read_file(&file_name)? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        toml::from_slice(&data)
+            .map(Some)
+            .map_err(|e| Error::TomlDe(e, format!("{:?}", file_name)))
+    }
+
+    fn save(&self, stem: &str, data: &[u8], metadata: &EntryMetadata) -> Result<(), Error> {
+        let file_name = self.make_file_name(stem, self.compression);
+        let data = self
+            .compression
+            .compress(data)
+            .map_err(|e| Error::Io(e, format!("compressing {:?}", file_name)))?;
+        let metadata = toml::to_vec(metadata)
+            .map_err(|e| Error::TomlSer(e, format!("metadata of {}", stem)))?;
+
+        let _lock = self.lock()?;
+        write_atomically(&file_name, &data)?;
+        write_atomically(&self.make_metadata_file_name(stem), &metadata)?;
+        // Copies stored before the compression has been changed would be stale now.
+        for &compression in Compression::ALL {
+            if compression == self.compression {
+                continue;
+            }
+            let file_name = self.make_file_name(stem, compression);
+            match fs::remove_file(&file_name) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(Error::Io(e, format!("removing {:?}", file_name)))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes an exclusive advisory lock of the cache directory, which is released when the returned
+    /// value is dropped. Only writers take the lock: files are replaced atomically, so readers
+    /// never see them half-written, and a read-only cache can still be read.
+    ///
+    /// Since the lock is taken on a separate file descriptor every time, it must not be taken
+    /// again while held, or the process will deadlock.
+    fn lock(&self) -> Result<Option<File>, Error> {
+        let storage_path = match &self.storage_path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let path = storage_path.join(LOCK_FILE_NAME);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| Error::Io(e, format!("opening {:?}", path)))?;
+        file.lock()
+            .map_err(|e| Error::Io(e, format!("locking {:?}", path)))?;
+        Ok(Some(file))
+    }
+}
+
+/// A name of a file used to lock the cache directory.
+const LOCK_FILE_NAME: &str = ".lock";
+
+/// An extension of temporary files created by [`write_atomically`].
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Reads a file, returns `None` if it doesn't exist, e.g. because it's been removed by another
+/// process.
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            log::debug!("File {:?} doesn't exist", path);
+            Ok(None)
+        }
+        Err(e) => Err(Error::Io(e, format!("reading {:?}", path))),
+    }
+}
+
+/// Writes a file via a temporary one, so the file is either fully written or not changed at all
+/// even if the process crashes.
+pub(crate) fn write_atomically(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.{}", process::id(), TEMP_EXTENSION));
+    let temp_path = path.with_file_name(temp_name);
+    let write = || -> io::Result<()> {
+        let mut f = File::create(&temp_path)?;
+        f.write_all(data)?;
+        f.sync_all()?;
+        fs::rename(&temp_path, path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        Error::Io(e, format!("writing to {:?}", path))
+    })
+}
+
+/// A file name stem of the latest manifest.
+const LATEST_STEM: &str = "latest";
+
+/// Makes a file name stem of a manifest for a given day.
+fn day_stem(day: NaiveDate) -> String {
+    day.format("%Y-%m-%d").to_string()
+}
+
+impl FsCache {
+    /// Lists all the cached entries sorted in descending order of dates.
+    ///
+    /// Files which names don't start with a date are ignored, as well as temporary files left by
+    /// interrupted writes.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>, Error> {
+        let storage_path = match &self.storage_path {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+        let read_dir = fs::read_dir(storage_path)
+            .map_err(|e| Error::Io(e, format!("reading directory {:?}", storage_path)))?;
+        let mut entries: BTreeMap<NaiveDate, CacheEntry> = BTreeMap::new();
+        for dir_entry in read_dir {
+            let dir_entry = dir_entry
+                .map_err(|e| Error::Io(e, format!("reading directory {:?}", storage_path)))?;
+            let path = dir_entry.path();
+            if path.extension().is_some_and(|ext| ext == TEMP_EXTENSION) {
+                continue;
+            }
+            let date = match path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.get(..10))
+                .and_then(|prefix| NaiveDate::parse_from_str(prefix, "%Y-%m-%d").ok())
+            {
+                Some(date) => date,
+                None => continue,
+            };
+            let metadata = dir_entry
+                .metadata()
+                .map_err(|e| Error::Io(e, format!("reading metadata of {:?}", path)))?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let entry = entries.entry(date).or_insert_with(|| CacheEntry {
+                date,
+                paths: Vec::new(),
+                size: 0,
+            });
+            entry.paths.push(path);
+            entry.size += metadata.len();
+        }
+        Ok(entries.into_values().rev().collect())
+    }
+
+    /// Checks all the cached entries and returns the ones that have problems.
+    pub fn verify(&self) -> Result<Vec<(CacheEntry, EntryProblem)>, Error> {
+        let mut problems = Vec::new();
+        for entry in self.entries()? {
+            let problem = match self.load(entry.date) {
+                Err(e) => Some(EntryProblem::Corrupt(e)),
+                Ok(None) => Some(EntryProblem::NoManifest),
+                Ok(Some(manifest)) if manifest.date != entry.date => {
+                    Some(EntryProblem::DateMismatch(manifest.date))
+                }
+                Ok(Some(_)) => self
+                    .metadata(entry.date)
+                    .err()
+                    .map(EntryProblem::CorruptMetadata),
+            };
+            if let Some(problem) = problem {
+                problems.push((entry, problem));
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Removes all the files of an entry.
+    pub fn remove(&self, entry: &CacheEntry) -> Result<(), Error> {
+        let _lock = self.lock()?;
+        self.remove_files(entry)
+    }
+
+    fn remove_files(&self, entry: &CacheEntry) -> Result<(), Error> {
+        for path in &entry.paths {
+            match fs::remove_file(path) {
+                // Might have been removed by another process before the lock has been taken.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                result => result.map_err(|e| Error::Io(e, format!("removing {:?}", path)))?,
+            }
+        }
+        log::debug!("Removed cached entry for {}", entry.date);
+        Ok(())
+    }
+
+    /// Removes the entries that are not allowed by a given retention policy. If `dry_run` is set,
+    /// nothing is actually removed.
+    ///
+    /// Returns a list of the (would-be) removed entries.
+    pub fn prune(&self, policy: &RetentionPolicy, dry_run: bool) -> Result<Vec<CacheEntry>, Error> {
+        let today = Utc::now().naive_utc().date();
+        let oldest_allowed = policy
+            .max_age_days
+            .and_then(|days| today.checked_sub_signed(Duration::days(days.into())));
+
+        let _lock = if dry_run { None } else { self.lock()? };
+        let mut total_size = 0;
+        let mut over_budget = false;
+        let mut removed = Vec::new();
+        for (idx, entry) in self.entries()?.into_iter().enumerate() {
+            // Once an entry doesn't fit, all the older ones are evicted as well, even if they are
+            // small enough.
+            over_budget = over_budget
+                || policy
+                    .max_size
+                    .is_some_and(|max_size| total_size + entry.size > max_size);
+            let keep = !over_budget
+                && policy.keep_days.is_none_or(|days| idx < days)
+                && oldest_allowed.is_none_or(|oldest| entry.date >= oldest);
+            if keep {
+                total_size += entry.size;
+                continue;
+            }
+            if !dry_run {
+                self.remove_files(&entry)?;
+            }
+            removed.push(entry);
+        }
+        Ok(removed)
+    }
+}
+
+/// Makes a cache in a fresh temporary directory, shared by the tests across the crate.
+#[cfg(test)]
+pub(crate) fn temp_cache(name: &str) -> (FsCache, PathBuf) {
+    let path = std::env::temp_dir().join(format!(
+        "rustup-available-packages-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&path);
+    (FsCache::new(&path).unwrap(), path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune() {
+        let (cache, path) = temp_cache("prune");
+        let today = Utc::now().naive_utc().date();
+        for days_ago in 0..5 {
+            let date = today - Duration::days(days_ago);
+            let name = date.format("%Y-%m-%d.toml").to_string();
+            fs::write(path.join(name), vec![0; 10]).unwrap();
+        }
+        fs::write(path.join("README"), "not an entry").unwrap();
+        let temp_name = today.format("%Y-%m-%d.toml.1234.tmp").to_string();
+        fs::write(path.join(&temp_name), vec![0; 100]).unwrap();
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(5, entries.len());
+        assert_eq!(today, entries[0].date);
+        assert_eq!(10, entries[0].size);
+
+        let policy = RetentionPolicy {
+            keep_days: Some(4),
+            max_size: Some(35),
+            max_age_days: None,
+        };
+        let removed = cache.prune(&policy, true).unwrap();
+        assert_eq!(2, removed.len());
+        assert_eq!(5, cache.entries().unwrap().len());
+
+        let removed = cache.prune(&policy, false).unwrap();
+        assert_eq!(2, removed.len());
+        assert_eq!(3, cache.entries().unwrap().len());
+
+        let policy = RetentionPolicy {
+            max_age_days: Some(1),
+            ..Default::default()
+        };
+        cache.prune(&policy, false).unwrap();
+        let dates: Vec<_> = cache
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.date)
+            .collect();
+        assert_eq!(vec![today, today - Duration::days(1)], dates);
+        assert!(path.join("README").exists());
+        assert!(path.join(temp_name).exists());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn prune_uneven_sizes() {
+        let (cache, path) = temp_cache("prune-uneven");
+        let today = Utc::now().naive_utc().date();
+        for (days_ago, size) in [10, 30, 5].iter().enumerate() {
+            let date = today - Duration::days(days_ago as i64);
+            let name = date.format("%Y-%m-%d.toml").to_string();
+            fs::write(path.join(name), vec![0; *size]).unwrap();
+        }
+
+        let policy = RetentionPolicy {
+            max_size: Some(35),
+            ..Default::default()
+        };
+        let removed: Vec<_> = cache
+            .prune(&policy, false)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.size)
+            .collect();
+        assert_eq!(vec![30, 5], removed);
+        let kept: Vec<_> = cache
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.size)
+            .collect();
+        assert_eq!(vec![10], kept);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn verify() {
+        let (cache, path) = temp_cache("verify");
+        let files = [
+            ("2019-03-01.toml", "date = \"2019-03-01\"\n[pkg]\n"),
+            ("2019-03-02.toml", "date = \"2019-03-01\"\n[pkg]\n"),
+            ("2019-03-03.toml", "date = \"2019-03-03\"\n[pkg.rls.tar"),
+            ("2019-03-04.toml.gz", "not a gzip"),
+            ("2019-03-05.meta.toml", "source_url = "),
+        ];
+        for (name, contents) in &files {
+            fs::write(path.join(name), contents).unwrap();
+        }
+
+        let problems = cache.verify().unwrap();
+        let summary: Vec<_> = problems
+            .iter()
+            .map(|(entry, problem)| (entry.date.format("%d").to_string(), problem))
+            .collect();
+        assert_eq!(4, summary.len());
+        assert!(matches!(summary[0], (ref day, EntryProblem::NoManifest) if day == "05"));
+        #[cfg(feature = "gzip")]
+        assert!(
+            matches!(summary[1], (ref day, EntryProblem::Corrupt(Error::Io(..))) if day == "04")
+        );
+        // Gzipped files are not recognized as manifests without the feature.
+        #[cfg(not(feature = "gzip"))]
+        assert!(matches!(summary[1], (ref day, EntryProblem::NoManifest) if day == "04"));
+        assert!(
+            matches!(summary[2], (ref day, EntryProblem::Corrupt(Error::TomlDe(..))) if day == "03")
+        );
+        assert!(matches!(summary[3], (ref day, EntryProblem::DateMismatch(_)) if day == "02"));
+
+        for (entry, _) in &problems {
+            cache.remove(entry).unwrap();
+        }
+        assert!(cache.verify().unwrap().is_empty());
+        assert_eq!(1, cache.entries().unwrap().len());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn lock() {
+        let (cache, path) = temp_cache("lock");
+        let day = NaiveDate::from_ymd_opt(2019, 3, 1).unwrap();
+        assert!(cache.load(day).unwrap().is_none());
+        assert!(cache.metadata(day).unwrap().is_none());
+        // Readers don't need the lock file, so a read-only cache can be read.
+        assert!(!path.join(LOCK_FILE_NAME).exists());
+
+        let lock = cache.lock().unwrap();
+        let other = File::open(path.join(LOCK_FILE_NAME)).unwrap();
+        assert!(other.try_lock_shared().is_err());
+        drop(lock);
+        other.try_lock().unwrap();
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "gzip", feature = "zstd"))]
+    fn compression() {
+        let (cache, path) = temp_cache("compression");
+        let manifest = |date| Manifest {
+            date,
+            packages: Default::default(),
+            renames: Default::default(),
+        };
+        let store = |cache: &FsCache, date| {
+            let data = toml::to_vec(&manifest(date)).unwrap();
+            let metadata = EntryMetadata::new("https://example.com/channel-rust-nightly.toml");
+            cache.store(date, &data, &metadata);
+        };
+        let plain = NaiveDate::from_ymd_opt(2019, 3, 1).unwrap();
+        store(&cache, plain);
+
+        let cache = cache.set_compression(Compression::Zstd);
+        let zstd = NaiveDate::from_ymd_opt(2019, 3, 2).unwrap();
+        store(&cache, zstd);
+        assert!(path.join("2019-03-02.toml.zst").exists());
+
+        let cache = cache.set_compression(Compression::Gzip);
+        let gzip = NaiveDate::from_ymd_opt(2019, 3, 3).unwrap();
+        store(&cache, gzip);
+        assert!(path.join("2019-03-03.toml.gz").exists());
+
+        for date in [plain, zstd, gzip] {
+            assert_eq!(Some(manifest(date)), cache.get(date));
+        }
+
+        // Storing a day again removes the copies with other compressions.
+        store(&cache, plain);
+        assert!(path.join("2019-03-01.toml.gz").exists());
+        assert!(!path.join("2019-03-01.toml").exists());
+        let entry = cache.entries().unwrap().pop().unwrap();
+        assert_eq!(plain, entry.date);
+        assert_eq!(2, entry.paths.len());
+        assert_eq!(
+            None,
+            cache.get(NaiveDate::from_ymd_opt(2019, 3, 4).unwrap())
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn raw_data() {
+        let (cache, path) = temp_cache("raw_data");
+        let date = NaiveDate::from_ymd_opt(2019, 3, 1).unwrap();
+        // Unknown fields are ignored by the parser but kept in the cache.
+        let data = br#"
+            date = "2019-03-01"
+            manifest-version = "2"
+            [pkg.rls.target.x86_64-unknown-linux-gnu]
+            available = true
+            url = "https://example.com/rls.tar.gz"
+        "#;
+        let url = "https://example.com/2019-03-01/channel-rust-nightly.toml";
+        cache.store(date, data, &EntryMetadata::new(url));
+
+        assert_eq!(
+            &data[..],
+            &fs::read(path.join("2019-03-01.toml")).unwrap()[..]
+        );
+        let manifest = cache.load(date).unwrap().unwrap();
+        assert!(manifest.packages["rls"].targets["x86_64-unknown-linux-gnu"].available);
+        let metadata = cache.metadata(date).unwrap().unwrap();
+        assert_eq!(url, metadata.source_url);
+
+        let missing = NaiveDate::from_ymd_opt(2019, 3, 2).unwrap();
+        assert_eq!(None, cache.load(missing).unwrap());
+        assert_eq!(None, cache.metadata(missing).unwrap());
+
+        fs::write(path.join("2019-03-02.toml"), "garbage").unwrap();
+        assert!(cache.load(missing).is_err());
+        assert_eq!(None, cache.get(missing));
+
+        assert_eq!(None, cache.get_latest());
+        let metadata = EntryMetadata {
+            etag: Some("\"abc\"".into()),
+            ..EntryMetadata::new("https://example.com/channel-rust-nightly.toml")
+        };
+        cache.store_latest(data, &metadata);
+        assert_eq!(Some((manifest, metadata)), cache.get_latest());
+        // The latest manifest is not an entry of any day.
+        assert_eq!(2, cache.entries().unwrap().len());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
 }
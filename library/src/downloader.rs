@@ -1,19 +1,33 @@
-use super::skip_errors::SkipMissingExt;
 use crate::{
-    cache::FsCache,
+    cache::{EntryMetadata, FsCache},
     manifest::Manifest,
+    missing::{MissingPolicy, SkipBudget, SkipMissing, SkippedDay},
     source::{DefaultSource, SourceInfo},
-    Error,
+    Error, ProgressObserver,
 };
 use chrono::{Duration, NaiveDate};
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
 use std::{io, iter};
 
+/// A result of a download.
+enum Fetched {
+    /// Downloaded data along with its metadata.
+    Data(Vec<u8>, EntryMetadata),
+    /// A cached copy is still up to date.
+    NotModified,
+}
+
 /// Manifests downloader and parser.
 pub struct Downloader<S> {
     client: reqwest::blocking::Client,
     source: S,
     cache: FsCache,
-    skip_missing_days: usize,
+    missing_policy: MissingPolicy,
+    offline: bool,
+    observer: Box<dyn ProgressObserver>,
 }
 
 impl<'a> Downloader<DefaultSource<'a>> {
@@ -30,7 +44,9 @@ impl<S> Downloader<S> {
             client: reqwest::blocking::Client::new(),
             source,
             cache: FsCache::noop(),
-            skip_missing_days: 0,
+            missing_policy: MissingPolicy::default(),
+            offline: false,
+            observer: Box::new(()),
         }
     }
 }
@@ -45,21 +61,74 @@ where
             client: self.client,
             source: self.source,
             cache: c,
-            skip_missing_days: self.skip_missing_days,
+            missing_policy: self.missing_policy,
+            offline: self.offline,
+            observer: self.observer,
         }
     }
 
     /// Set to non zero if you want to silently skip days for which manifest files are missing.
     /// Not more than `skip` days will be skipped.
-    /// Please not that this setting only affects the [`get_last_manifests`] method.
+    /// Please note that this setting only affects the methods that get manifests for multiple days,
+    /// see [`missing_policy`](Self::missing_policy).
     ///
-    /// Off (zero) by default.
+    /// A shortcut for a [`MissingPolicy`] with a [`SkipBudget::Total`] budget. Off (zero) by
+    /// default.
     pub fn skip_missing_days(self, skip: usize) -> Downloader<S> {
+        self.missing_policy(MissingPolicy::new(SkipBudget::Total(skip)))
+    }
+
+    /// Sets a policy of skipping missing manifests. Please note that this setting only affects the
+    /// methods that get manifests for multiple days, i.e.
+    /// [`get_last_manifests`](Self::get_last_manifests),
+    /// [`get_last_manifests_with_skipped`](Self::get_last_manifests_with_skipped),
+    /// [`iter_last_manifests`](Self::iter_last_manifests),
+    /// [`get_manifests_between`](Self::get_manifests_between) and
+    /// [`iter_manifests_between`](Self::iter_manifests_between).
+    ///
+    /// By default no manifests are skipped.
+    pub fn missing_policy(self, policy: MissingPolicy) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_policy: policy,
+            offline: self.offline,
+            observer: self.observer,
+        }
+    }
+
+    /// Enables the offline mode, in which manifests are only read from the cache and the network
+    /// is never touched. The latest manifest is then the most recent one in the cache.
+    ///
+    /// Manifests missing in the cache are treated the same way as the ones missing on the server,
+    /// i.e. they are skipped according to the [`missing_policy`](Self::missing_policy) and
+    /// result in [`Error::NotCached`] otherwise.
+    ///
+    /// Off by default.
+    pub fn offline(self, offline: bool) -> Downloader<S> {
         Downloader {
             client: self.client,
             source: self.source,
             cache: self.cache,
-            skip_missing_days: skip,
+            missing_policy: self.missing_policy,
+            offline,
+            observer: self.observer,
+        }
+    }
+
+    /// Sets an observer that is notified about every manifest being retrieved, e.g. to show a
+    /// progress bar.
+    ///
+    /// By default nobody is notified.
+    pub fn progress_observer(self, observer: impl ProgressObserver + 'static) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_policy: self.missing_policy,
+            offline: self.offline,
+            observer: Box::new(observer),
         }
     }
 
@@ -68,51 +137,559 @@ where
     ///
     /// The returned vector is sorted in descending order of dates.
     pub fn get_last_manifests(&self, days: usize) -> Result<Vec<Manifest>, Error> {
+        self.get_last_manifests_with_skipped(days)
+            .map(|(manifests, _)| manifests)
+    }
+
+    /// Same as [`get_last_manifests`](Self::get_last_manifests), but also returns the days that
+    /// have been skipped according to the [`missing_policy`](Self::missing_policy), in descending
+    /// order.
+    pub fn get_last_manifests_with_skipped(
+        &self,
+        days: usize,
+    ) -> Result<(Vec<Manifest>, Vec<SkippedDay>), Error> {
+        let mut manifests = self.iter_last_manifests(days)?;
+        let collected = manifests.by_ref().collect::<Result<_, _>>()?;
+        Ok((collected, manifests.into_skipped()))
+    }
+
+    /// Same as [`get_last_manifests`](Self::get_last_manifests), but manifests are downloaded
+    /// lazily while iterating, so they don't have to be kept in memory all at once, e.g.:
+    ///
+    /// ```no_run
+    /// # use rustup_available_packages::{AvailabilityData, Downloader};
+    /// let downloader = Downloader::with_default_source("nightly");
+    /// let mut data = AvailabilityData::default();
+    /// data.try_add_manifests(downloader.iter_last_manifests(30)?)?;
+    /// # Ok::<(), rustup_available_packages::Error>(())
+    /// ```
+    ///
+    /// Only the latest manifest is fetched right away, since it defines the dates.
+    #[allow(clippy::type_complexity)]
+    pub fn iter_last_manifests(
+        &self,
+        days: usize,
+    ) -> Result<
+        SkipMissing<'_, impl Iterator<Item = (NaiveDate, Result<Manifest, Error>)> + '_>,
+        Error,
+    > {
         let latest = self.get_latest_manifest()?;
         let latest_day = latest.date;
         log::info!("Latest manifest is for {}", latest_day);
         let rest = (1..days)
-            .filter_map(|day| latest_day.checked_sub_signed(Duration::days(day as i64)))
-            .map(|date| self.get_manifest(date))
-            .skip_missing(self.skip_missing_days);
-        iter::once(Ok(latest)).chain(rest).collect()
+            .filter_map(move |day| latest_day.checked_sub_signed(Duration::days(day as i64)))
+            .map(move |date| (date, self.get_manifest(date)));
+        Ok(SkipMissing::new(
+            iter::once((latest_day, Ok(latest))).chain(rest),
+            &self.missing_policy,
+            &*self.observer,
+        ))
+    }
+
+    /// Gets manifests for all the dates from `from` to `to`, both inclusive. Missing manifests are
+    /// skipped according to the [`missing_policy`](Self::missing_policy).
+    ///
+    /// The returned vector is sorted in descending order of dates.
+    pub fn get_manifests_between(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Manifest>, Error> {
+        self.iter_manifests_between(from, to).collect()
+    }
+
+    /// Same as [`get_manifests_between`](Self::get_manifests_between), but manifests are
+    /// downloaded lazily while iterating.
+    pub fn iter_manifests_between(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> SkipMissing<'_, impl Iterator<Item = (NaiveDate, Result<Manifest, Error>)> + '_> {
+        let days = (to - from).num_days();
+        let dates = (0..=days).filter_map(move |day| to.checked_sub_signed(Duration::days(day)));
+        SkipMissing::new(
+            dates.map(move |date| (date, self.get_manifest(date))),
+            &self.missing_policy,
+            &*self.observer,
+        )
     }
 
     /// Gets manifest for a given date.
+    ///
+    /// If the source provides fallback URLs, they are tried in order while the previous ones
+    /// respond with 404 or 5xx. The URL that has served the manifest is recorded in the cache
+    /// metadata, see [`FsCache::metadata`], and is returned by
+    /// [`get_manifest_with_source`](Self::get_manifest_with_source).
     pub fn get_manifest(&self, day: NaiveDate) -> Result<Manifest, Error> {
+        self.retrieve_manifest(day).map(|(manifest, _)| manifest)
+    }
+
+    /// Same as [`get_manifest`](Self::get_manifest), but also returns the URL that has served the
+    /// manifest.
+    ///
+    /// For a cached manifest the URL is taken from the cache metadata, so it's `None` if the
+    /// manifest has been cached without metadata, e.g. by an older version of the library.
+    pub fn get_manifest_with_source(
+        &self,
+        day: NaiveDate,
+    ) -> Result<(Manifest, Option<String>), Error> {
+        let (manifest, source) = self.retrieve_manifest(day)?;
+        let source = source.or_else(|| {
+            self.cache
+                .metadata(day)
+                .map_err(|e| log::warn!("Can't load metadata: {}", e))
+                .ok()
+                .flatten()
+                .map(|metadata| metadata.source_url)
+        });
+        Ok((manifest, source))
+    }
+
+    /// Gets manifest for a given date along with the URL it's been downloaded from, unless it's
+    /// been cached.
+    fn retrieve_manifest(&self, day: NaiveDate) -> Result<(Manifest, Option<String>), Error> {
+        let result = self.load_or_download_manifest(day);
+        if let Err(e) = &result {
+            self.observer.failed(Some(day), e);
+        }
+        result
+    }
+
+    /// Gets manifest for a given date either from the cache or from the network.
+    fn load_or_download_manifest(
+        &self,
+        day: NaiveDate,
+    ) -> Result<(Manifest, Option<String>), Error> {
         if let Some(cached) = self.cache.get(day) {
-            return Ok(cached);
+            self.observer.cached(day);
+            return Ok((cached, None));
         }
-        let manifest = self.get_manifest_by_url(self.source.make_manifest_url(day))?;
-        self.cache.store(&manifest);
-        Ok(manifest)
+        if self.offline {
+            return Err(Error::NotCached(day));
+        }
+        let url = self.source.make_manifest_url(day);
+        let fallbacks = self.source.make_fallback_manifest_urls(day);
+        self.observer.started(Some(day));
+        let (data, url) = with_fallback(url.as_ref(), &fallbacks, |url| self.fetch_data(url))?;
+        let manifest: Manifest =
+            toml::from_slice(&data).map_err(|e| Error::TomlDe(e, url.to_string()))?;
+        // The manifest is cached under the requested day, since that's how it's looked up, even if
+        // the server has returned a manifest for another date.
+        self.cache.store(day, &data, &EntryMetadata::new(url));
+        self.observer.finished(day, data.len() as u64);
+        Ok((manifest, Some(url.to_string())))
     }
 
-    /// Gets manifest for a given date. If the `date` is `None`, the latest available manifest is
-    /// requested.
+    /// Gets the latest available manifest.
     ///
-    /// This call is never cached.
+    /// If a cache is set, the request is conditional, i.e. the manifest is not downloaded again if
+    /// it hasn't changed since the last call to the same URL. Fallback URLs are tried the same way
+    /// as in [`get_manifest`](Self::get_manifest). In the offline mode, the most recent cached manifest
+    /// is returned.
     pub fn get_latest_manifest(&self) -> Result<Manifest, Error> {
-        self.get_manifest_by_url(self.source.make_latest_manifest_url())
+        let result = self.retrieve_latest_manifest();
+        if let Err(e) = &result {
+            self.observer.failed(None, e);
+        }
+        result
+    }
+
+    /// Gets the latest manifest either from the cache or from the network.
+    fn retrieve_latest_manifest(&self) -> Result<Manifest, Error> {
+        if self.offline {
+            let manifest = self
+                .cache
+                .entries()?
+                .into_iter()
+                .find_map(|entry| self.cache.get(entry.date))
+                .ok_or_else(|| {
+                    Error::Offline("determine the latest date, no manifests are cached".into())
+                })?;
+            self.observer.cached(manifest.date);
+            return Ok(manifest);
+        }
+        let primary = self.source.make_latest_manifest_url();
+        let fallbacks = self.source.make_fallback_latest_manifest_urls();
+        let cached = self.cache.get_latest();
+        self.observer.started(None);
+        let (fetched, url) = with_fallback(primary.as_ref(), &fallbacks, |url| {
+            let cached = cached.as_ref().map(|(_, metadata)| metadata);
+            self.fetch(url, cached.filter(|metadata| metadata.source_url == url))
+        })?;
+        let (data, metadata) = match fetched {
+            Fetched::Data(data, metadata) => (data, metadata),
+            Fetched::NotModified => {
+                log::info!("The latest manifest has not been modified");
+                // `NotModified` is only returned for conditional requests.
+                let manifest = cached.unwrap().0;
+                self.observer.cached(manifest.date);
+                return Ok(manifest);
+            }
+        };
+        let manifest: Manifest =
+            toml::from_slice(&data).map_err(|e| Error::TomlDe(e, url.to_string()))?;
+        self.cache.store_latest(&data, &metadata);
+        if !self.cache.contains(manifest.date) {
+            // The dated manifest is recorded as downloaded from the same source, but from its own
+            // URL. The latest manifest's ETag and Last-Modified don't apply to it.
+            let source = fallbacks
+                .iter()
+                .position(|fallback| fallback.as_ref() == url)
+                .map_or(0, |idx| idx + 1);
+            let dated_url = iter::once(self.source.make_manifest_url(manifest.date))
+                .chain(self.source.make_fallback_manifest_urls(manifest.date))
+                .nth(source);
+            if let Some(dated_url) = dated_url {
+                let metadata = EntryMetadata::new(dated_url.as_ref());
+                self.cache.store(manifest.date, &data, &metadata);
+            }
+        }
+        self.observer.finished(manifest.date, data.len() as u64);
+        Ok(manifest)
     }
 
     /// Fetches a manifest from a given url.
     ///
-    /// This call is never cached.
+    /// This call is never cached, hence it always fails in the offline mode.
     pub fn get_manifest_by_url(&self, url: impl AsRef<str>) -> Result<Manifest, Error> {
         let url = url.as_ref();
+        let data = self.fetch_data(url)?;
+        toml::from_slice(&data).map_err(|e| Error::TomlDe(e, url.to_string()))
+    }
+
+    /// Downloads a file from a given url.
+    fn fetch_data(&self, url: &str) -> Result<Vec<u8>, Error> {
+        match self.fetch(url, None)? {
+            Fetched::Data(data, _) => Ok(data),
+            Fetched::NotModified => Err(Error::BadResponse(StatusCode::NOT_MODIFIED, url.into())),
+        }
+    }
+
+    /// Downloads a file from a given url. If metadata of a previously downloaded copy is provided,
+    /// the request is conditional.
+    fn fetch(&self, url: &str, cached: Option<&EntryMetadata>) -> Result<Fetched, Error> {
+        if self.offline {
+            return Err(Error::Offline(format!("download {}", url)));
+        }
         log::info!("Fetching a manifest from {}", url);
-        let mut response = self
-            .client
-            .get(url)
-            .send()
-            .map_err(|e| Error::Reqwest(e, url.into()))?;
+        let mut request = self.client.get(url);
+        if let Some(cached) = cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let mut response = request.send().map_err(|e| Error::Reqwest(e, url.into()))?;
+        if cached.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
         if !response.status().is_success() {
             return Err(Error::BadResponse(response.status(), url.into()));
         }
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let metadata = EntryMetadata {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            ..EntryMetadata::new(url)
+        };
         let mut bytes = Vec::new();
         io::copy(&mut response, &mut bytes).map_err(|e| Error::Io(e, url.into()))?;
+        Ok(Fetched::Data(bytes, metadata))
+    }
+}
+
+/// Calls `fetch` for the `primary` URL and then for the `fallbacks` in order while they respond
+/// with 404 or 5xx. Returns the last result along with the URL it's been got from.
+fn with_fallback<'u, T>(
+    primary: &'u str,
+    fallbacks: &'u [impl AsRef<str>],
+    mut fetch: impl FnMut(&str) -> Result<T, Error>,
+) -> Result<(T, &'u str), Error> {
+    let mut result = fetch(primary).map(|x| (x, primary));
+    for url in fallbacks.iter().map(AsRef::as_ref) {
+        match &result {
+            Err(Error::BadResponse(status, failed_url))
+                if *status == StatusCode::NOT_FOUND || status.is_server_error() =>
+            {
+                log::info!(
+                    "{} responded with {}, falling back to {}",
+                    failed_url,
+                    status,
+                    url
+                );
+            }
+            _ => break,
+        }
+        result = fetch(url).map(|x| (x, url));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::temp_cache, AvailabilityData};
+    use std::{
+        borrow::Cow,
+        fs,
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    /// Starts a local HTTP server and returns its base URL. Dated manifests are only served under
+    /// `/fallback`, and `/latest` serves the manifest of 2019-03-05 with an ETag, answering 304 to
+    /// a matching `If-None-Match`. Everything else is not found.
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(&stream).lines();
+                let request = lines.next().unwrap().unwrap();
+                let mut not_modified = false;
+                loop {
+                    let header = lines.next().unwrap().unwrap();
+                    if header.is_empty() {
+                        break;
+                    }
+                    not_modified |= header.eq_ignore_ascii_case("if-none-match: \"v1\"");
+                }
+                let path = request.split_whitespace().nth(1).unwrap();
+                let date = path
+                    .strip_prefix("/fallback/")
+                    .and_then(|rest| rest.split('/').next());
+                let (status, headers, body) = match date {
+                    Some(date) => ("200 OK", "", format!("date = \"{}\"\n[pkg]\n", date)),
+                    None if path == "/latest/channel-rust-nightly.toml" && not_modified => {
+                        ("304 Not Modified", "", String::new())
+                    }
+                    None if path == "/latest/channel-rust-nightly.toml" => (
+                        "200 OK",
+                        "ETag: \"v1\"\r\n",
+                        "date = \"2019-03-05\"\n[pkg]\n".to_string(),
+                    ),
+                    None => ("404 Not Found", "", String::new()),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[derive(Default, Clone)]
+    struct Events(Arc<Mutex<Vec<String>>>);
+
+    impl Events {
+        fn push(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    impl ProgressObserver for Events {
+        fn cached(&self, date: NaiveDate) {
+            self.push(format!("cached {}", date));
+        }
+
+        fn failed(&self, date: Option<NaiveDate>, _: &Error) {
+            self.push(format!("failed {:?}", date));
+        }
+
+        fn skipped(&self, date: NaiveDate, _: &Error) {
+            self.push(format!("skipped {}", date));
+        }
+    }
+
+    #[test]
+    fn offline() {
+        let (cache, path) = temp_cache("offline");
+        let downloader = Downloader::with_default_source("nightly")
+            .set_cache(cache)
+            .offline(true);
+        assert!(matches!(
+            downloader.get_latest_manifest(),
+            Err(Error::Offline(_))
+        ));
+
+        for day in &[1, 2, 4] {
+            let contents = format!("date = \"2019-03-0{}\"\n[pkg]\n", day);
+            fs::write(path.join(format!("2019-03-0{}.toml", day)), contents).unwrap();
+        }
+        let date = |day| NaiveDate::from_ymd_opt(2019, 3, day).unwrap();
+        assert_eq!(date(4), downloader.get_latest_manifest().unwrap().date);
+        assert!(matches!(
+            downloader.get_manifest(date(3)),
+            Err(Error::NotCached(day)) if day == date(3)
+        ));
+        assert!(downloader.get_last_manifests(4).is_err());
+        let mut data = AvailabilityData::default();
+        data.try_add_manifests(downloader.iter_last_manifests(1).unwrap())
+            .unwrap();
+        assert!(data.get_available_packages().is_empty());
+        assert!(data
+            .try_add_manifests(downloader.iter_last_manifests(2).unwrap())
+            .is_err());
+
+        let downloader = downloader.skip_missing_days(1);
+        let (manifests, skipped) = downloader.get_last_manifests_with_skipped(4).unwrap();
+        let dates: Vec<_> = manifests
+            .into_iter()
+            .map(|manifest| manifest.date)
+            .collect();
+        assert_eq!(vec![date(4), date(2), date(1)], dates);
+        assert_eq!(1, skipped.len());
+        assert_eq!(date(3), skipped[0].date);
+        assert!(matches!(skipped[0].reason, Error::NotCached(_)));
+
+        let dates = |manifests: Vec<Manifest>| -> Vec<_> {
+            manifests
+                .into_iter()
+                .map(|manifest| manifest.date)
+                .collect()
+        };
+        assert_eq!(
+            vec![date(2), date(1)],
+            dates(downloader.get_manifests_between(date(1), date(2)).unwrap())
+        );
+        assert!(downloader
+            .get_manifests_between(date(3), date(2))
+            .unwrap()
+            .is_empty());
+        let mut iter = downloader.iter_manifests_between(date(1), date(4));
+        assert_eq!(date(4), iter.next().unwrap().unwrap().date);
+        assert!(iter.skipped().is_empty());
+        assert_eq!(date(2), iter.next().unwrap().unwrap().date);
+        assert_eq!(date(3), iter.skipped()[0].date);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn progress() {
+        let (cache, path) = temp_cache("progress");
+        let events = Events::default();
+        let downloader = Downloader::with_default_source("nightly")
+            .set_cache(cache)
+            .offline(true)
+            .skip_missing_days(1)
+            .progress_observer(events.clone());
+        assert!(downloader.get_latest_manifest().is_err());
+        assert_eq!(vec!["failed None"], events.take());
+
+        for day in &[1, 2, 4] {
+            let contents = format!("date = \"2019-03-0{}\"\n[pkg]\n", day);
+            fs::write(path.join(format!("2019-03-0{}.toml", day)), contents).unwrap();
+        }
+        assert_eq!(3, downloader.get_last_manifests(4).unwrap().len());
+        assert_eq!(
+            vec![
+                "cached 2019-03-04",
+                "failed Some(2019-03-03)",
+                "skipped 2019-03-03",
+                "cached 2019-03-02",
+                "cached 2019-03-01",
+            ],
+            events.take()
+        );
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn fallback() {
+        let fetch = |url: &str| match url {
+            "mirror" => Err(Error::BadResponse(StatusCode::NOT_FOUND, url.into())),
+            "broken" => Err(Error::BadResponse(StatusCode::BAD_GATEWAY, url.into())),
+            "forbidden" => Err(Error::BadResponse(StatusCode::FORBIDDEN, url.into())),
+            url => Ok(url.len()),
+        };
+        let no_fallbacks: &[&str] = &[];
+        assert_eq!(4, with_fallback("main", no_fallbacks, fetch).unwrap().0);
+        assert!(with_fallback("mirror", no_fallbacks, fetch).is_err());
+        assert_eq!(
+            (4, "main"),
+            with_fallback("mirror", &["broken", "main", "other"], fetch).unwrap()
+        );
+        assert!(matches!(
+            with_fallback("mirror", &["forbidden", "main"], fetch),
+            Err(Error::BadResponse(StatusCode::FORBIDDEN, _))
+        ));
+        assert!(matches!(
+            with_fallback("mirror", &["broken"], fetch),
+            Err(Error::BadResponse(StatusCode::BAD_GATEWAY, _))
+        ));
+    }
+
+    #[test]
+    fn fallback_source() {
+        let base_url = serve();
+        let mut source = DefaultSource::new("nightly");
+        source.override_base(Cow::Owned(format!("{}/primary", base_url)));
+        source.add_fallback_base(Cow::Owned(format!("{}/fallback", base_url)));
+        let day = NaiveDate::from_ymd_opt(2019, 3, 1).unwrap();
+        let expected_url = format!("{}/fallback/2019-03-01/channel-rust-nightly.toml", base_url);
+
+        let downloader = Downloader::new(source);
+        let (manifest, url) = downloader.get_manifest_with_source(day).unwrap();
+        assert_eq!(day, manifest.date);
+        assert_eq!(Some(&expected_url), url.as_ref());
+
+        // The source of a cached manifest is taken from its metadata.
+        let (cache, path) = temp_cache("fallback");
+        let downloader = downloader.set_cache(cache);
+        assert_eq!(day, downloader.get_manifest(day).unwrap().date);
+        let downloader = downloader.offline(true);
+        let (_, url) = downloader.get_manifest_with_source(day).unwrap();
+        assert_eq!(Some(expected_url), url);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn not_modified() {
+        let base_url = serve();
+        let mut source = DefaultSource::new("nightly");
+        source.override_base(Cow::Owned(format!("{}/latest", base_url)));
+        let (cache, path) = temp_cache("not-modified");
+        let events = Events::default();
+        let downloader = Downloader::new(source)
+            .set_cache(cache)
+            .progress_observer(events.clone());
+        let date = |day| NaiveDate::from_ymd_opt(2019, 3, day).unwrap();
+        assert_eq!(date(5), downloader.get_latest_manifest().unwrap().date);
+        assert!(events.take().is_empty());
+
+        // Tamper with the stored copy to tell it apart from the served one. The server answers
+        // the conditional request with 304, so the copy is returned and not stored again.
+        let latest = path.join("latest.toml");
+        let tampered = "date = \"2019-03-04\"\n[pkg]\n";
+        fs::write(&latest, tampered).unwrap();
+        assert_eq!(date(4), downloader.get_latest_manifest().unwrap().date);
+        assert_eq!(vec!["cached 2019-03-04"], events.take());
+        assert_eq!(tampered, fs::read_to_string(&latest).unwrap());
 
-        toml::from_slice(&bytes).map_err(|e| Error::TomlDe(e, url.to_string()))
+        fs::remove_dir_all(&path).unwrap();
     }
 }
@@ -0,0 +1,105 @@
+//! Include/exclude filters for package and target names.
+
+use serde::{Deserialize, Serialize};
+
+/// A filter that accepts or rejects names based on lists of patterns.
+///
+/// A pattern is either an exact name or a glob, where `*` matches any (possibly empty) sequence of
+/// characters and `?` matches exactly one character.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Filter {
+    /// If not empty, only the names that match at least one of the patterns are accepted.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Names that match any of the patterns are rejected, even if they are included explicitly.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Filter {
+    /// Checks whether a given name is accepted by the filter.
+    pub fn matches(&self, name: &str) -> bool {
+        let included =
+            self.include.is_empty() || self.include.iter().any(|pattern| glob_match(pattern, name));
+        included && !self.exclude.iter().any(|pattern| glob_match(pattern, name))
+    }
+
+    /// Checks whether the filter accepts any name, i.e. it has no patterns at all.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+/// Matches a name against a glob pattern.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the position in the name it has been matched
+    // against, so we can backtrack and let the star consume one more character.
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("rls", "rls"));
+        assert!(!glob_match("rls", "rls-preview"));
+        assert!(glob_match("*-preview", "rls-preview"));
+        assert!(glob_match("*-preview", "-preview"));
+        assert!(!glob_match("*-preview", "rls-preview-2"));
+        assert!(glob_match("rust-*", "rust-src"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("x86_64-*-linux-*", "x86_64-unknown-linux-gnu"));
+        assert!(!glob_match("x86_64-*-linux-*", "x86_64-unknown-freebsd"));
+        assert!(glob_match("wasm3?-*", "wasm32-unknown-unknown"));
+        assert!(!glob_match("wasm3?-*", "wasm3-unknown"));
+    }
+
+    #[test]
+    fn include_exclude() {
+        let filter = Filter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches("rls-preview"));
+
+        let filter = Filter {
+            include: vec![],
+            exclude: vec!["*-preview".into()],
+        };
+        assert!(filter.matches("rls"));
+        assert!(!filter.matches("rls-preview"));
+
+        let filter = Filter {
+            include: vec!["rust-*".into(), "miri".into()],
+            exclude: vec!["rust-docs".into()],
+        };
+        assert!(filter.matches("rust-src"));
+        assert!(filter.matches("miri"));
+        assert!(!filter.matches("rust-docs"));
+        assert!(!filter.matches("rls"));
+    }
+}
@@ -0,0 +1,420 @@
+//! An SQLite-backed store of packages availability history.
+//!
+//! Unlike [`AvailabilityData`](crate::AvailabilityData), the history is kept on disk and can be
+//! queried with plain SQL, e.g.:
+//!
+//! ```sql
+//! SELECT date, version FROM availability
+//! WHERE channel = 'nightly' AND package = 'rls' AND target = 'x86_64-unknown-linux-gnu'
+//! ORDER BY date DESC;
+//! ```
+//!
+//! The `availability` table has the following columns: `date` (a `%Y-%m-%d` string), `channel`,
+//! `target`, `package`, `available` (0 or 1) and `version` (nullable).
+
+use crate::{
+    availability::{AvailabilityRow, AvailabilityStats},
+    manifest::Manifest,
+    types::TargetComponents,
+    Error,
+};
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS availability (
+    date TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    target TEXT NOT NULL,
+    package TEXT NOT NULL,
+    available INTEGER NOT NULL,
+    version TEXT,
+    PRIMARY KEY (channel, date, target, package)
+);
+CREATE INDEX IF NOT EXISTS availability_by_package
+    ON availability (channel, package, target, date);
+";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A store of packages availability history of a channel.
+pub struct HistoryStore {
+    connection: Connection,
+    channel: String,
+}
+
+impl HistoryStore {
+    /// Opens a store at a given path for a given channel. The database is created if it doesn't
+    /// exist.
+    pub fn open(path: impl AsRef<Path>, channel: impl Into<String>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let connection =
+            Connection::open(path).map_err(|e| Error::Sqlite(e, format!("opening {:?}", path)))?;
+        Self::with_connection(connection, channel)
+    }
+
+    /// Opens an in-memory store for a given channel.
+    pub fn open_in_memory(channel: impl Into<String>) -> Result<Self, Error> {
+        let connection = Connection::open_in_memory()
+            .map_err(|e| Error::Sqlite(e, "opening an in-memory database".into()))?;
+        Self::with_connection(connection, channel)
+    }
+
+    fn with_connection(connection: Connection, channel: impl Into<String>) -> Result<Self, Error> {
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| Error::Sqlite(e, "creating the schema".into()))?;
+        Ok(HistoryStore {
+            connection,
+            channel: channel.into(),
+        })
+    }
+
+    /// A connection to the database to run arbitrary queries.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Adds an availability data from a given [`Manifest`], replacing the data for the same date
+    /// if any.
+    pub fn add_manifest(&mut self, manifest: &Manifest) -> Result<(), Error> {
+        let context = || format!("adding the manifest for {}", manifest.date);
+        let reverse_renames: HashMap<_, _> = manifest
+            .renames
+            .iter()
+            .map(|(key, value)| (value.to.as_str(), key.as_str()))
+            .collect();
+        let date = manifest.date.format(DATE_FORMAT).to_string();
+
+        let transaction = self
+            .connection
+            .transaction()
+            .map_err(|e| Error::Sqlite(e, context()))?;
+        {
+            let mut insert = transaction
+                .prepare(
+                    "INSERT OR REPLACE INTO availability \
+                     (date, channel, target, package, available, version) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(|e| Error::Sqlite(e, context()))?;
+            for (package_name, info) in &manifest.packages {
+                let package_name = reverse_renames
+                    .get(package_name.as_str())
+                    .copied()
+                    .unwrap_or(package_name);
+                for (target, target_info) in &info.targets {
+                    insert
+                        .execute(params![
+                            date,
+                            self.channel,
+                            target,
+                            package_name,
+                            target_info.available,
+                            info.version,
+                        ])
+                        .map_err(|e| Error::Sqlite(e, context()))?;
+                }
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|e| Error::Sqlite(e, context()))
+    }
+
+    /// Adds multiple [`Manifest`]s at once.
+    pub fn add_manifests<'a>(
+        &mut self,
+        manifests: impl IntoIterator<Item = &'a Manifest>,
+    ) -> Result<(), Error> {
+        manifests
+            .into_iter()
+            .try_for_each(|manifest| self.add_manifest(manifest))
+    }
+
+    /// Gets a list of dates the history has data for, sorted in descending order.
+    pub fn get_dates(&self) -> Result<Vec<NaiveDate>, Error> {
+        self.query_strings(
+            "SELECT DISTINCT date FROM availability WHERE channel = ?1 ORDER BY date DESC",
+            params![self.channel],
+        )?
+        .iter()
+        .map(|date| parse_date(date))
+        .collect()
+    }
+
+    /// Gets a list of targets that have ever had an available package, except for the '*' target.
+    pub fn get_available_targets(&self) -> Result<HashSet<String>, Error> {
+        Ok(self
+            .query_strings(
+                "SELECT DISTINCT target FROM availability \
+                 WHERE channel = ?1 AND available AND target != '*'",
+                params![self.channel],
+            )?
+            .into_iter()
+            .collect())
+    }
+
+    /// Gets a list of targets (except for the '*' target) whose components satisfy a given
+    /// predicate.
+    pub fn get_targets_by<F>(&self, mut predicate: F) -> Result<HashSet<String>, Error>
+    where
+        F: FnMut(&TargetComponents<'_>) -> bool,
+    {
+        Ok(self
+            .get_available_targets()?
+            .into_iter()
+            .filter(|target| predicate(&TargetComponents::parse(target)))
+            .collect())
+    }
+
+    /// Gets a list of targets for a given architecture, e.g. `aarch64`.
+    pub fn get_targets_by_arch(&self, arch: &str) -> Result<HashSet<String>, Error> {
+        self.get_targets_by(|components| components.arch == arch)
+    }
+
+    /// Gets a list of targets for a given operating system, e.g. `windows`.
+    pub fn get_targets_by_os(&self, os: &str) -> Result<HashSet<String>, Error> {
+        self.get_targets_by(|components| components.os == Some(os))
+    }
+
+    /// Returns all packages that have ever been available on any target.
+    pub fn get_available_packages(&self) -> Result<HashSet<String>, Error> {
+        Ok(self
+            .query_strings(
+                "SELECT DISTINCT package FROM availability WHERE channel = ?1 AND available",
+                params![self.channel],
+            )?
+            .into_iter()
+            .collect())
+    }
+
+    /// Makes a row of availabilities of a given package on a given target for given dates, see
+    /// [`AvailabilityData::get_availability_row`](crate::AvailabilityData::get_availability_row).
+    pub fn get_availability_row<'a, I>(
+        &self,
+        target: &str,
+        pkg: &'a str,
+        dates: I,
+    ) -> Result<Option<AvailabilityRow<'a>>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<NaiveDate>,
+    {
+        if !self.has_been_available(target, pkg)? {
+            return Ok(None);
+        }
+        Ok(Some(AvailabilityRow::from_available_dates(
+            pkg,
+            &self.available_dates(target, pkg)?,
+            dates,
+        )))
+    }
+
+    /// Evaluates availability statistics of a given package on a given target over the given dates,
+    /// see [`AvailabilityData::get_availability_stats`](crate::AvailabilityData::get_availability_stats).
+    pub fn get_availability_stats<I>(
+        &self,
+        target: &str,
+        pkg: &str,
+        dates: I,
+    ) -> Result<Option<AvailabilityStats>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<NaiveDate>,
+    {
+        if !self.has_been_available(target, pkg)? {
+            return Ok(None);
+        }
+        Ok(Some(AvailabilityStats::from_available_dates(
+            &self.available_dates(target, pkg)?,
+            dates,
+        )))
+    }
+
+    /// Finds when a given package was last available on a given target.
+    ///
+    /// Availability is checked against the specified target and against the `*` target.
+    pub fn last_available(&self, target: &str, pkg: &str) -> Result<Option<NaiveDate>, Error> {
+        let date: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT MAX(date) FROM availability \
+                 WHERE channel = ?1 AND package = ?2 AND target IN (?3, '*') AND available",
+                params![self.channel, pkg, target],
+                |row| row.get(0),
+            )
+            .map_err(|e| Error::Sqlite(e, format!("querying {} on {}", pkg, target)))?;
+        date.as_deref().map(parse_date).transpose()
+    }
+
+    /// Finds a version of a given package on a given date.
+    pub fn version(&self, pkg: &str, date: NaiveDate) -> Result<Option<String>, Error> {
+        self.connection
+            .query_row(
+                "SELECT version FROM availability \
+                 WHERE channel = ?1 AND package = ?2 AND date = ?3 AND version IS NOT NULL \
+                 LIMIT 1",
+                params![self.channel, pkg, date.format(DATE_FORMAT).to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| Error::Sqlite(e, format!("querying a version of {}", pkg)))
+    }
+
+    /// Checks whether a given package has ever been available on exactly the given target.
+    fn has_been_available(&self, target: &str, pkg: &str) -> Result<bool, Error> {
+        self.connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM availability \
+                 WHERE channel = ?1 AND package = ?2 AND target = ?3 AND available)",
+                params![self.channel, pkg, target],
+                |row| row.get(0),
+            )
+            .map_err(|e| Error::Sqlite(e, format!("querying {} on {}", pkg, target)))
+    }
+
+    /// Retrieves a set of all the dates when a given package was available on a given target or
+    /// on the `*` target.
+    fn available_dates(&self, target: &str, pkg: &str) -> Result<HashSet<NaiveDate>, Error> {
+        self.query_strings(
+            "SELECT DISTINCT date FROM availability \
+             WHERE channel = ?1 AND package = ?2 AND target IN (?3, '*') AND available",
+            params![self.channel, pkg, target],
+        )?
+        .iter()
+        .map(|date| parse_date(date))
+        .collect()
+    }
+
+    /// Runs a query that returns a single column of strings.
+    fn query_strings(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<String>, Error> {
+        let context = || format!("running {:?}", sql);
+        let mut statement = self
+            .connection
+            .prepare_cached(sql)
+            .map_err(|e| Error::Sqlite(e, context()))?;
+        let rows = statement
+            .query_map(params, |row| row.get(0))
+            .map_err(|e| Error::Sqlite(e, context()))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| Error::Sqlite(e, context()))
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT).map_err(|e| {
+        Error::Sqlite(
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into()),
+            format!("parsing date {:?}", date),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AvailabilityData;
+
+    const MANIFESTS: [&str; 3] = [
+        r#"date = "2019-03-01"
+[pkg.rls-preview]
+version = "1.33.0 (abc 2019-03-01)"
+[pkg.rls-preview.target.x86_64-unknown-linux-gnu]
+available = true
+[pkg.rust-src.target."*"]
+available = true
+[renames.rls]
+to = "rls-preview"
+"#,
+        r#"date = "2019-03-02"
+[pkg.rls-preview.target.x86_64-unknown-linux-gnu]
+available = false
+[pkg.rls-preview.target.aarch64-unknown-linux-gnu]
+available = true
+[pkg.rust-src.target."*"]
+available = true
+[renames.rls]
+to = "rls-preview"
+"#,
+        r#"date = "2019-03-03"
+[pkg.rls-preview.target.x86_64-unknown-linux-gnu]
+available = true
+[renames.rls]
+to = "rls-preview"
+"#,
+    ];
+
+    #[test]
+    fn mirrors_availability_data() {
+        let manifests: Vec<Manifest> = MANIFESTS
+            .iter()
+            .map(|data| toml::from_str(data).unwrap())
+            .collect();
+        let mut store = HistoryStore::open_in_memory("nightly").unwrap();
+        store.add_manifests(&manifests).unwrap();
+        // Adding a manifest again doesn't duplicate anything.
+        store.add_manifest(&manifests[0]).unwrap();
+        let mut data = AvailabilityData::default();
+        data.add_manifests(manifests);
+
+        let dates = store.get_dates().unwrap();
+        assert_eq!(3, dates.len());
+        assert_eq!(NaiveDate::from_ymd_opt(2019, 3, 3).unwrap(), dates[0]);
+
+        let owned =
+            |set: HashSet<&str>| -> HashSet<String> { set.into_iter().map(String::from).collect() };
+        assert_eq!(
+            owned(data.get_available_targets()),
+            store.get_available_targets().unwrap()
+        );
+        assert_eq!(
+            owned(data.get_available_packages()),
+            store.get_available_packages().unwrap()
+        );
+        assert_eq!(
+            owned(data.get_targets_by_arch("aarch64")),
+            store.get_targets_by_arch("aarch64").unwrap()
+        );
+
+        for target in ["x86_64-unknown-linux-gnu", "aarch64-unknown-linux-gnu", "*"] {
+            for pkg in ["rls", "rust-src", "miri"] {
+                let expected = data.get_availability_row(target, pkg, &dates);
+                let actual = store.get_availability_row(target, pkg, &dates).unwrap();
+                assert_eq!(
+                    expected.map(|row| (row.availability_list, row.last_available)),
+                    actual.map(|row| (row.availability_list, row.last_available)),
+                    "{} on {}",
+                    pkg,
+                    target
+                );
+                assert_eq!(
+                    data.get_availability_stats(target, pkg, &dates),
+                    store.get_availability_stats(target, pkg, &dates).unwrap()
+                );
+                assert_eq!(
+                    data.last_available(target, pkg),
+                    store.last_available(target, pkg).unwrap()
+                );
+            }
+        }
+
+        assert_eq!(
+            Some("1.33.0 (abc 2019-03-01)".to_string()),
+            store.version("rls", dates[2]).unwrap()
+        );
+        assert_eq!(None, store.version("rls", dates[0]).unwrap());
+
+        let other_channel = HistoryStore::open_in_memory("beta").unwrap();
+        assert!(other_channel.get_dates().unwrap().is_empty());
+    }
+}
@@ -8,25 +8,37 @@
 //! specific dates and targets.
 //!
 //! Suggestions and critiques are welcome!
+//!
+//! The minimum supported Rust version is 1.89 since 0.3.0.
 
 #![deny(missing_docs)]
 
 pub mod availability;
 pub mod cache;
 mod downloader;
+pub mod filter;
+#[cfg(feature = "sqlite")]
+pub mod history;
 pub mod manifest;
-mod skip_errors;
+mod missing;
+mod progress;
 mod source;
 pub mod table;
 mod types;
 
 pub use availability::AvailabilityData;
 pub use downloader::Downloader;
+pub use missing::{MissingPolicy, SkipBudget, SkipMissing, SkippedDay};
+pub use progress::ProgressObserver;
 pub use source::{DefaultSource, SourceInfo};
 use std::io;
+pub use types::TargetComponents;
 
 /// An error that might happen inside the library.
+///
+/// New variants might be added in minor releases, e.g. depending on the enabled features.
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     /// TOML parsing error.
     #[error("TOML deserialization error {0} on manifest {1}")]
@@ -47,4 +59,17 @@ pub enum Error {
     /// I/O error.
     #[error("I/O error {0} at {1}")]
     Io(#[source] io::Error, String),
+
+    /// SQLite error.
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error {0} at {1}")]
+    Sqlite(#[source] rusqlite::Error, String),
+
+    /// A manifest is not cached and can't be downloaded in the offline mode.
+    #[error("manifest for {0} is not cached and can't be downloaded in the offline mode")]
+    NotCached(chrono::NaiveDate),
+
+    /// An action requires network access, which is disabled in the offline mode.
+    #[error("can't {0} in the offline mode")]
+    Offline(String),
 }
@@ -3,15 +3,10 @@
 //! Currently only fields required to get an availability status are implemented, but if you need
 //! more please feel free to send a PR.
 
-use crate::Error;
+use crate::{cache::write_atomically, Error};
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{Read, Write},
-    path::Path,
-};
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
 
 /// A rustup manifest.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -35,8 +30,14 @@ pub struct Rename {
 }
 
 /// Package info.
+///
+/// More fields might be added in minor releases, so it can only be constructed by deserializing.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct PackageTargets {
+    /// Version of the package, like `1.40.0-nightly (1423bec54 2019-11-05)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     /// Maps targets onto package availability info.
     #[serde(rename = "target")]
     pub targets: HashMap<String, PackageInfo>,
@@ -61,14 +62,12 @@ impl Manifest {
     }
 
     /// Serializes the `Manifest` to a given path.
+    ///
+    /// The file is written atomically, i.e. it is never left half-written.
     pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), Error> {
-        let path = path.as_ref();
-        let mut f = File::create(path).map_err(|e| Error::Io(e, format!("creating {:?}", path)))?;
         let data = toml::to_vec(self)
             .map_err(|e| Error::TomlSer(e, format!("serializing {}", self.date)))?;
-        f.write_all(&data)
-            .map_err(|e| Error::Io(e, format!("writing to {:?}", path)))?;
-        Ok(())
+        write_atomically(path.as_ref(), &data)
     }
 }
 
@@ -105,11 +104,12 @@ to = "rustfmt-preview"
 "#;
         let parsed_manifest: Manifest = toml::from_str(data).unwrap();
         let reference_manifest = Manifest {
-            date: NaiveDate::from_ymd(2018, 9, 3),
+            date: NaiveDate::from_ymd_opt(2018, 9, 3).unwrap(),
             packages: vec![
                 (
                     "cargo".to_string(),
                     PackageTargets {
+                        version: None,
                         targets: vec![
                             (
                                 "aarch64-unknown-linux-gnu".to_string(),
@@ -131,6 +131,7 @@ to = "rustfmt-preview"
                 (
                     "rustfmt-preview".to_string(),
                     PackageTargets {
+                        version: None,
                         targets: vec![
                             (
                                 "aarch64-unknown-linux-gnu".to_string(),
@@ -184,10 +185,11 @@ xz_hash = "dbb913da0a207ae80c53bc6a42074b637920c2a80121420416579fed3e7f2499"
 "#;
         let parsed_manifest: Manifest = toml::from_str(data).unwrap();
         let reference_manifest = Manifest {
-            date: NaiveDate::from_ymd(2018, 9, 3),
+            date: NaiveDate::from_ymd_opt(2018, 9, 3).unwrap(),
             packages: vec![(
                 "rust-src".to_string(),
                 PackageTargets {
+                    version: None,
                     targets: vec![("*".to_string(), PackageInfo { available: true })]
                         .into_iter()
                         .collect(),
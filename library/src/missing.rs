@@ -0,0 +1,232 @@
+use crate::{Error, ProgressObserver};
+use chrono::NaiveDate;
+use reqwest::StatusCode;
+
+/// Limits the number of missing manifests that can be skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipBudget {
+    /// Not more than this many days are skipped in total.
+    Total(usize),
+    /// Not more than this many consecutive days are skipped, but there might be any number of such
+    /// gaps.
+    PerGap(usize),
+}
+
+/// Defines which manifests are considered missing and how many of them can be skipped.
+///
+/// By default only the manifests that the server responds with 404 for (and the ones missing in
+/// the cache in the offline mode) are considered missing, and none of them are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingPolicy {
+    budget: SkipBudget,
+    statuses: Vec<StatusCode>,
+    parse_errors: bool,
+}
+
+impl Default for MissingPolicy {
+    fn default() -> Self {
+        MissingPolicy::new(SkipBudget::Total(0))
+    }
+}
+
+impl MissingPolicy {
+    /// Initializes a policy with a given budget.
+    pub fn new(budget: SkipBudget) -> Self {
+        MissingPolicy {
+            budget,
+            statuses: vec![StatusCode::NOT_FOUND],
+            parse_errors: false,
+        }
+    }
+
+    /// Sets HTTP statuses which mean that a manifest is missing. Only 404 is by default.
+    pub fn missing_statuses(self, statuses: impl IntoIterator<Item = StatusCode>) -> Self {
+        MissingPolicy {
+            budget: self.budget,
+            statuses: statuses.into_iter().collect(),
+            parse_errors: self.parse_errors,
+        }
+    }
+
+    /// Set to `true` if you want manifests that can't be parsed to be considered missing.
+    ///
+    /// Off by default.
+    pub fn parse_errors_as_missing(self, parse_errors: bool) -> Self {
+        MissingPolicy {
+            budget: self.budget,
+            statuses: self.statuses,
+            parse_errors,
+        }
+    }
+
+    /// Gets the budget of the policy.
+    pub fn budget(&self) -> SkipBudget {
+        self.budget
+    }
+
+    /// Checks whether an error means that a manifest is missing.
+    pub fn is_missing(&self, error: &Error) -> bool {
+        match error {
+            Error::BadResponse(status, _) => self.statuses.contains(status),
+            Error::NotCached(_) => true,
+            Error::TomlDe(..) => self.parse_errors,
+            _ => false,
+        }
+    }
+}
+
+/// A day that has been skipped because of a missing manifest.
+#[derive(Debug)]
+pub struct SkippedDay {
+    /// The day of the missing manifest.
+    pub date: NaiveDate,
+    /// Why the manifest is considered missing.
+    pub reason: Error,
+}
+
+/// An iterator wrapper to skip missing manifests according to a [`MissingPolicy`].
+///
+/// Missing manifests are skipped lazily, so the days skipped so far are available while iterating.
+pub struct SkipMissing<'a, I> {
+    inner: I,
+    policy: &'a MissingPolicy,
+    observer: &'a dyn ProgressObserver,
+    skipped: Vec<SkippedDay>,
+    gap: usize,
+}
+
+impl<'a, I> SkipMissing<'a, I> {
+    /// Creates a wrapper over an iterator of manifests paired with their dates. Skipped days are
+    /// reported to the `observer`.
+    pub(crate) fn new(
+        inner: I,
+        policy: &'a MissingPolicy,
+        observer: &'a dyn ProgressObserver,
+    ) -> Self {
+        SkipMissing {
+            inner,
+            policy,
+            observer,
+            skipped: Vec::new(),
+            gap: 0,
+        }
+    }
+
+    /// Returns the days skipped so far.
+    pub fn skipped(&self) -> &[SkippedDay] {
+        &self.skipped
+    }
+
+    /// Consumes the iterator and returns the days skipped so far.
+    pub fn into_skipped(self) -> Vec<SkippedDay> {
+        self.skipped
+    }
+
+    fn can_skip(&self) -> bool {
+        match self.policy.budget {
+            SkipBudget::Total(days) => self.skipped.len() < days,
+            SkipBudget::PerGap(days) => self.gap < days,
+        }
+    }
+}
+
+impl<I, T> Iterator for SkipMissing<'_, I>
+where
+    I: Iterator<Item = (NaiveDate, Result<T, Error>)>,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                (_, Ok(x)) => {
+                    self.gap = 0;
+                    break Some(Ok(x));
+                }
+                (date, Err(e)) if self.policy.is_missing(&e) && self.can_skip() => {
+                    log::warn!("Missing a manifest for {}: {}", date, e);
+                    self.gap += 1;
+                    self.observer.skipped(date, &e);
+                    self.skipped.push(SkippedDay { date, reason: e });
+                }
+                (_, Err(e)) => break Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(policy: &MissingPolicy, statuses: &[u16]) -> (Result<Vec<u32>, Error>, Vec<u32>) {
+        let results = statuses.iter().enumerate().map(|(idx, &status)| {
+            let date = NaiveDate::from_ymd_opt(2019, 3, idx as u32 + 1).unwrap();
+            let result = match status {
+                200 => Ok(date.format("%d").to_string().parse().unwrap()),
+                0 => Err(Error::TomlDe(
+                    toml::from_str::<u32>("").unwrap_err(),
+                    String::new(),
+                )),
+                status => Err(Error::BadResponse(
+                    StatusCode::from_u16(status).unwrap(),
+                    String::new(),
+                )),
+            };
+            (date, result)
+        });
+        let mut iter = SkipMissing::new(results, policy, &());
+        let result = iter.by_ref().collect();
+        let skipped = iter
+            .into_skipped()
+            .into_iter()
+            .map(|day| day.date.format("%d").to_string().parse().unwrap())
+            .collect();
+        (result, skipped)
+    }
+
+    #[test]
+    fn budget() {
+        let statuses = [200, 404, 404, 200, 404, 200];
+        let (result, skipped) = run(&MissingPolicy::default(), &statuses);
+        assert!(result.is_err());
+        assert!(skipped.is_empty());
+
+        let (result, skipped) = run(&MissingPolicy::new(SkipBudget::Total(3)), &statuses);
+        assert_eq!(vec![1, 4, 6], result.unwrap());
+        assert_eq!(vec![2, 3, 5], skipped);
+
+        let (result, _) = run(&MissingPolicy::new(SkipBudget::Total(2)), &statuses);
+        assert!(result.is_err());
+
+        let (result, skipped) = run(&MissingPolicy::new(SkipBudget::PerGap(2)), &statuses);
+        assert_eq!(vec![1, 4, 6], result.unwrap());
+        assert_eq!(vec![2, 3, 5], skipped);
+
+        let (result, skipped) = run(&MissingPolicy::new(SkipBudget::PerGap(1)), &statuses);
+        assert!(matches!(
+            result,
+            Err(Error::BadResponse(StatusCode::NOT_FOUND, _))
+        ));
+        assert_eq!(vec![2], skipped);
+    }
+
+    #[test]
+    fn missing_errors() {
+        let statuses = [200, 403, 0, 200];
+        let policy = MissingPolicy::new(SkipBudget::Total(5));
+        assert!(run(&policy, &statuses).0.is_err());
+
+        let policy = policy.missing_statuses(vec![StatusCode::NOT_FOUND, StatusCode::FORBIDDEN]);
+        assert!(run(&policy, &statuses).0.is_err());
+
+        let policy = policy.parse_errors_as_missing(true);
+        let (result, skipped) = run(&policy, &statuses);
+        assert_eq!(vec![1, 4], result.unwrap());
+        assert_eq!(vec![2, 3], skipped);
+        assert!(!policy.is_missing(&Error::BadResponse(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            String::new()
+        )));
+    }
+}
@@ -0,0 +1,28 @@
+use crate::Error;
+use chrono::NaiveDate;
+
+/// An observer of the [`Downloader`](crate::Downloader) progress.
+///
+/// All the methods do nothing by default, so you only need to implement the ones you are
+/// interested in. The unit type `()` is an observer that ignores everything.
+#[allow(unused_variables)]
+pub trait ProgressObserver: Send + Sync {
+    /// A download of a manifest has started. The `date` is `None` for the latest manifest, since
+    /// its date is not known until it's downloaded.
+    fn started(&self, date: Option<NaiveDate>) {}
+
+    /// A manifest has been downloaded, `bytes` is the size of the downloaded data.
+    fn finished(&self, date: NaiveDate, bytes: u64) {}
+
+    /// A manifest has been taken from the cache without downloading it.
+    fn cached(&self, date: NaiveDate) {}
+
+    /// A manifest can't be retrieved. The `date` is `None` if the latest manifest has failed.
+    fn failed(&self, date: Option<NaiveDate>, error: &Error) {}
+
+    /// A day has been skipped according to the [`MissingPolicy`](crate::MissingPolicy). Always
+    /// follows a [`failed`](Self::failed) call for the same date.
+    fn skipped(&self, date: NaiveDate, reason: &Error) {}
+}
+
+impl ProgressObserver for () {}
@@ -11,12 +11,29 @@ pub trait SourceInfo {
 
     /// Makes a URL for the latest manifest.
     fn make_latest_manifest_url(&self) -> Self::Url;
+
+    /// Makes URLs to fall back to, in order, if the one from
+    /// [`make_manifest_url`](Self::make_manifest_url) responds with 404 or 5xx.
+    ///
+    /// None by default.
+    fn make_fallback_manifest_urls(&self, _: NaiveDate) -> Vec<Self::Url> {
+        Vec::new()
+    }
+
+    /// Makes URLs to fall back to, in order, if the one from
+    /// [`make_latest_manifest_url`](Self::make_latest_manifest_url) responds with 404 or 5xx.
+    ///
+    /// None by default.
+    fn make_fallback_latest_manifest_urls(&self) -> Vec<Self::Url> {
+        Vec::new()
+    }
 }
 
 /// Default source, i.e. `https://static.rust-lang.org/...`.
 pub struct DefaultSource<'a> {
     channel: &'a str,
     base_url: Cow<'a, str>,
+    fallback_base_urls: Vec<Cow<'a, str>>,
 }
 
 impl<'a> DefaultSource<'a> {
@@ -28,6 +45,7 @@ impl<'a> DefaultSource<'a> {
         DefaultSource {
             channel,
             base_url: Cow::Borrowed(Self::DEFAULT_BASE_URL),
+            fallback_base_urls: Vec::new(),
         }
     }
 
@@ -35,19 +53,46 @@ impl<'a> DefaultSource<'a> {
     pub fn override_base(&mut self, base_url: Cow<'a, str>) {
         self.base_url = base_url
     }
+
+    /// Adds a base URL to fall back to if the previous ones respond with 404 or 5xx, e.g. when
+    /// the base URL is overridden with a mirror that might lag behind [`DEFAULT_BASE_URL`].
+    ///
+    /// [`DEFAULT_BASE_URL`]: Self::DEFAULT_BASE_URL
+    pub fn add_fallback_base(&mut self, base_url: Cow<'a, str>) {
+        self.fallback_base_urls.push(base_url)
+    }
+
+    fn manifest_url(&self, base_url: &str, date: NaiveDate) -> String {
+        format!("{}/{}/channel-rust-{}.toml", base_url, date, self.channel)
+    }
+
+    fn latest_manifest_url(&self, base_url: &str) -> String {
+        format!("{}/channel-rust-{}.toml", base_url, self.channel)
+    }
 }
 
 impl<'a> SourceInfo for DefaultSource<'a> {
     type Url = String;
 
     fn make_manifest_url(&self, date: NaiveDate) -> Self::Url {
-        format!(
-            "{}/{}/channel-rust-{}.toml",
-            self.base_url, date, self.channel
-        )
+        self.manifest_url(&self.base_url, date)
     }
 
     fn make_latest_manifest_url(&self) -> Self::Url {
-        format!("{}/channel-rust-{}.toml", self.base_url, self.channel)
+        self.latest_manifest_url(&self.base_url)
+    }
+
+    fn make_fallback_manifest_urls(&self, date: NaiveDate) -> Vec<Self::Url> {
+        self.fallback_base_urls
+            .iter()
+            .map(|base_url| self.manifest_url(base_url, date))
+            .collect()
+    }
+
+    fn make_fallback_latest_manifest_urls(&self) -> Vec<Self::Url> {
+        self.fallback_base_urls
+            .iter()
+            .map(|base_url| self.latest_manifest_url(base_url))
+            .collect()
     }
 }
@@ -1,8 +1,15 @@
 //! A table of statuses.
 
-use crate::availability::{AvailabilityData, AvailabilityRow};
+use crate::{
+    availability::{AvailabilityData, AvailabilityRow},
+    filter::Filter,
+};
 use chrono::NaiveDate;
-use std::{borrow::Borrow, iter};
+use std::{
+    borrow::Borrow,
+    io::{self, Write},
+    iter,
+};
 
 /// A ready-to-render table of packages statuses per target.
 #[derive(Debug, serde::Serialize)]
@@ -11,12 +18,68 @@ pub struct Table<'a, Additional: 'a = ()> {
     pub current_target: &'a str,
     /// Table's title.
     pub title: Vec<String>,
-    /// A list of packages and their availabilities sorted by package name in an ascending order.
+    /// A list of packages and their availabilities sorted according to the [`SortOrder`] (by
+    /// package name in an ascending order by default).
     pub packages_availability: Vec<AvailabilityRow<'a>>,
     /// Additional data to render.
     pub additional: Additional,
 }
 
+/// A plain-text format a [`Table`] can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma-separated values, as described in RFC 4180.
+    Csv,
+    /// A GitHub-flavored markdown table.
+    Markdown,
+    /// A plain ASCII table.
+    Ascii,
+}
+
+/// An order in which packages are listed in a [`Table`].
+///
+/// Packages that are equal according to the order are sorted by name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// By package name in an ascending order.
+    #[default]
+    Name,
+    /// Packages that haven't been available for the longest time go first. Packages that have never
+    /// been available precede all the others.
+    LastAvailable,
+    /// Packages that are missing on the first of the table's dates go first.
+    CurrentAvailability,
+    /// Packages that have been available on the fewest of the table's dates go first.
+    AvailabilityRatio,
+    /// Packages are listed in the given order. Packages that are not in the list follow.
+    Priority(Vec<String>),
+}
+
+impl SortOrder {
+    /// Sorts rows that are already sorted by package name.
+    fn sort(&self, rows: &mut [AvailabilityRow]) {
+        match self {
+            SortOrder::Name => {}
+            SortOrder::LastAvailable => rows.sort_by_key(|row| row.last_available),
+            SortOrder::CurrentAvailability => {
+                rows.sort_by_key(|row| row.availability_list.first().copied().unwrap_or(false))
+            }
+            SortOrder::AvailabilityRatio => rows.sort_by_key(|row| {
+                row.availability_list
+                    .iter()
+                    .filter(|&&available| available)
+                    .count()
+            }),
+            SortOrder::Priority(priority) => rows.sort_by_key(|row| {
+                priority
+                    .iter()
+                    .position(|pkg| pkg == row.package_name)
+                    .unwrap_or(priority.len())
+            }),
+        }
+    }
+}
+
 /// Sorts a given container (in a form of an iterator) into a vector of its items in an ascending
 /// order.
 fn sort<T: Ord>(data: impl IntoIterator<Item = T>) -> Vec<T> {
@@ -25,6 +88,15 @@ fn sort<T: Ord>(data: impl IntoIterator<Item = T>) -> Vec<T> {
     vec
 }
 
+/// Settings that define which packages are listed in a table.
+#[derive(Debug, Clone, Default)]
+struct PackagesSelection {
+    filter: Filter,
+    hide_unseen: bool,
+    order: SortOrder,
+    stats: bool,
+}
+
 /// Table builder.
 #[derive(Debug, Clone)]
 pub struct TableBuilder<'a, Dates = iter::Empty<NaiveDate>, DateFmt = &'static str, Additional = ()>
@@ -35,6 +107,7 @@ pub struct TableBuilder<'a, Dates = iter::Empty<NaiveDate>, DateFmt = &'static s
     first_cell: String,
     date_fmt: DateFmt,
     additional_data: Additional,
+    packages: PackagesSelection,
 }
 
 impl<'a> TableBuilder<'a> {
@@ -50,6 +123,7 @@ impl<'a> TableBuilder<'a> {
             first_cell: String::new(),
             date_fmt: "%Y-%m-%d",
             additional_data: (),
+            packages: PackagesSelection::default(),
         }
     }
 }
@@ -80,6 +154,7 @@ impl<'a, Dates, DateFmt, Additional> TableBuilder<'a, Dates, DateFmt, Additional
             first_cell: self.first_cell,
             date_fmt: self.date_fmt,
             additional_data: self.additional_data,
+            packages: self.packages,
         }
     }
 
@@ -99,9 +174,44 @@ impl<'a, Dates, DateFmt, Additional> TableBuilder<'a, Dates, DateFmt, Additional
             first_cell: self.first_cell,
             date_fmt,
             additional_data: self.additional_data,
+            packages: self.packages,
         }
     }
 
+    /// Sets a filter that selects which packages are listed in the table.
+    ///
+    /// By default all the packages are listed.
+    pub fn packages(mut self, filter: Filter) -> Self {
+        self.packages.filter = filter;
+        self
+    }
+
+    /// If set to `true`, packages that haven't been available on any of the table's dates are not
+    /// listed.
+    ///
+    /// Off by default.
+    pub fn hide_unseen(mut self, hide: bool) -> Self {
+        self.packages.hide_unseen = hide;
+        self
+    }
+
+    /// Sets an order in which packages are listed in the table.
+    ///
+    /// The default is [`SortOrder::Name`].
+    pub fn sort_by(mut self, order: SortOrder) -> Self {
+        self.packages.order = order;
+        self
+    }
+
+    /// If set to `true`, availability statistics over the table's dates are evaluated for every
+    /// package (see [`AvailabilityRow::stats`]).
+    ///
+    /// Off by default.
+    pub fn stats(mut self, stats: bool) -> Self {
+        self.packages.stats = stats;
+        self
+    }
+
     /// Sets the additional data.
     pub fn additional<NewAdditional>(
         self,
@@ -114,6 +224,7 @@ impl<'a, Dates, DateFmt, Additional> TableBuilder<'a, Dates, DateFmt, Additional
             first_cell: self.first_cell,
             date_fmt: self.date_fmt,
             additional_data: data,
+            packages: self.packages,
         }
     }
 
@@ -131,6 +242,7 @@ impl<'a, Dates, DateFmt, Additional> TableBuilder<'a, Dates, DateFmt, Additional
             self.first_cell,
             self.date_fmt.as_ref(),
             self.additional_data,
+            &self.packages,
         )
     }
 }
@@ -151,6 +263,7 @@ impl<'a, Additional> Table<'a, Additional> {
         first_cell: String,
         date_fmt: &str,
         additional_data: Additional,
+        packages: &PackagesSelection,
     ) -> Self
     where
         I: Iterator + Clone,
@@ -163,11 +276,24 @@ impl<'a, Additional> Table<'a, Additional> {
                     .map(|date| date.borrow().format(date_fmt).to_string()),
             )
             .collect();
-        let packages = sort(data.get_available_packages());
-        let availability = packages
+        let package_names = sort(
+            data.get_available_packages()
+                .into_iter()
+                .filter(|pkg| packages.filter.matches(pkg)),
+        );
+        let mut availability: Vec<_> = package_names
             .into_iter()
             .filter_map(|pkg| data.get_availability_row(target, pkg, dates.clone()))
+            .filter(|row| !packages.hide_unseen || row.availability_list.contains(&true))
+            .map(|mut row| {
+                if packages.stats {
+                    row.stats =
+                        data.get_availability_stats(target, row.package_name, dates.clone());
+                }
+                row
+            })
             .collect();
+        packages.order.sort(&mut availability);
         Table {
             current_target: target,
             title,
@@ -176,3 +302,281 @@ impl<'a, Additional> Table<'a, Additional> {
         }
     }
 }
+
+impl<'a, Additional> Table<'a, Additional> {
+    /// Writes the table in a given format.
+    ///
+    /// Additional data is not exported. The last column contains the date when a package was
+    /// available for the last time, or `N/A` if it wasn't available at all.
+    pub fn export(&self, format: ExportFormat, out: &mut impl Write) -> io::Result<()> {
+        let header: Vec<&str> = self
+            .title
+            .iter()
+            .map(String::as_str)
+            .chain(iter::once("Last available"))
+            .collect();
+        let rows: Vec<Vec<String>> = self
+            .packages_availability
+            .iter()
+            .map(|row| {
+                iter::once(row.package_name.to_string())
+                    .chain(row.availability_list.iter().map(|&available| {
+                        if available { "present" } else { "missing" }.to_string()
+                    }))
+                    .chain(iter::once(
+                        row.last_available
+                            .map(|date| date.to_string())
+                            .unwrap_or_else(|| "N/A".to_string()),
+                    ))
+                    .collect()
+            })
+            .collect();
+        match format {
+            ExportFormat::Csv => write_csv(&header, &rows, out),
+            ExportFormat::Markdown => write_markdown(&header, &rows, out),
+            ExportFormat::Ascii => write_ascii(&header, &rows, out),
+        }
+    }
+}
+
+fn write_csv(header: &[&str], rows: &[Vec<String>], out: &mut impl Write) -> io::Result<()> {
+    fn escape(cell: &str) -> String {
+        if cell.contains(&[',', '"', '\r', '\n'][..]) {
+            format!("\"{}\"", cell.replace('"', "\"\""))
+        } else {
+            cell.to_string()
+        }
+    }
+
+    // RFC 4180 requires CRLF line breaks.
+    let header = header.iter().map(|cell| escape(cell)).collect::<Vec<_>>();
+    write!(out, "{}\r\n", header.join(","))?;
+    for row in rows {
+        let row = row.iter().map(|cell| escape(cell)).collect::<Vec<_>>();
+        write!(out, "{}\r\n", row.join(","))?;
+    }
+    Ok(())
+}
+
+fn write_markdown(header: &[&str], rows: &[Vec<String>], out: &mut impl Write) -> io::Result<()> {
+    fn escape(cell: &str) -> String {
+        cell.replace('|', "\\|")
+    }
+
+    let header = header.iter().map(|cell| escape(cell)).collect::<Vec<_>>();
+    writeln!(out, "| {} |", header.join(" | "))?;
+    writeln!(out, "|{}", "---|".repeat(header.len()))?;
+    for row in rows {
+        let row = row.iter().map(|cell| escape(cell)).collect::<Vec<_>>();
+        writeln!(out, "| {} |", row.join(" | "))?;
+    }
+    Ok(())
+}
+
+fn write_ascii(header: &[&str], rows: &[Vec<String>], out: &mut impl Write) -> io::Result<()> {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let separator = widths
+        .iter()
+        .map(|&width| "-".repeat(width + 2))
+        .collect::<Vec<_>>()
+        .join("+");
+    let write_row = |out: &mut dyn Write, row: &mut dyn Iterator<Item = &str>| {
+        let cells = row
+            .zip(&widths)
+            .map(|(cell, &width)| format!(" {:width$} ", cell, width = width))
+            .collect::<Vec<_>>();
+        writeln!(out, "|{}|", cells.join("|"))
+    };
+
+    writeln!(out, "+{}+", separator)?;
+    write_row(out, &mut header.iter().copied())?;
+    writeln!(out, "+{}+", separator)?;
+    for row in rows {
+        write_row(out, &mut row.iter().map(String::as_str))?;
+    }
+    writeln!(out, "+{}+", separator)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+
+    fn availability() -> AvailabilityData {
+        let data = r#"date = "2018-09-03"
+[pkg.rust-std.target.lol]
+available = true
+[pkg.rls.target.lol]
+available = true
+"#;
+        let manifest: Manifest = toml::from_str(data).unwrap();
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(manifest);
+        availability
+    }
+
+    fn export(format: ExportFormat) -> String {
+        let availability = availability();
+        let dates = vec![
+            NaiveDate::from_ymd_opt(2018, 9, 4).unwrap(),
+            NaiveDate::from_ymd_opt(2018, 9, 3).unwrap(),
+        ];
+        let table = Table::builder(&availability, "lol")
+            .dates(&dates)
+            .first_cell(&"lol, really")
+            .build();
+        let mut out = Vec::new();
+        table.export(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            export(ExportFormat::Csv),
+            "\"lol, really\",2018-09-04,2018-09-03,Last available\r\n\
+             rls,missing,present,2018-09-03\r\n\
+             rust-std,missing,present,2018-09-03\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_quoting() {
+        let rows = vec![vec![
+            "say \"hi\"".to_string(),
+            "two\nlines".to_string(),
+            "plain".to_string(),
+        ]];
+        let mut out = Vec::new();
+        write_csv(&["a,b", "c", "d"], &rows, &mut out).unwrap();
+        assert_eq!(
+            "\"a,b\",c,d\r\n\"say \"\"hi\"\"\",\"two\nlines\",plain\r\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            export(ExportFormat::Markdown),
+            "\
+| lol, really | 2018-09-04 | 2018-09-03 | Last available |
+|---|---|---|---|
+| rls | missing | present | 2018-09-03 |
+| rust-std | missing | present | 2018-09-03 |
+"
+        );
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(
+            export(ExportFormat::Ascii),
+            "\
++-------------+------------+------------+----------------+
+| lol, really | 2018-09-04 | 2018-09-03 | Last available |
++-------------+------------+------------+----------------+
+| rls         | missing    | present    | 2018-09-03     |
+| rust-std    | missing    | present    | 2018-09-03     |
++-------------+------------+------------+----------------+
+"
+        );
+    }
+
+    #[test]
+    fn filter_packages() {
+        let availability = availability();
+        let names = |table: Table| -> Vec<String> {
+            table
+                .packages_availability
+                .iter()
+                .map(|row| row.package_name.to_string())
+                .collect()
+        };
+
+        let table = Table::builder(&availability, "lol")
+            .packages(Filter {
+                include: vec!["rust-*".into()],
+                exclude: vec![],
+            })
+            .build();
+        assert_eq!(names(table), vec!["rust-std"]);
+
+        let old = vec![NaiveDate::from_ymd_opt(2018, 9, 2).unwrap()];
+        let table = Table::builder(&availability, "lol").dates(&old).build();
+        assert_eq!(names(table), vec!["rls", "rust-std"]);
+        let table = Table::builder(&availability, "lol")
+            .dates(&old)
+            .hide_unseen(true)
+            .build();
+        assert!(names(table).is_empty());
+    }
+
+    #[test]
+    fn sort_order() {
+        let manifests = [
+            r#"date = "2018-09-03"
+[pkg.a-stable.target.lol]
+available = true
+[pkg.b-flaky.target.lol]
+available = true
+"#,
+            r#"date = "2018-09-02"
+[pkg.a-stable.target.lol]
+available = true
+[pkg.c-broken.target.lol]
+available = true
+"#,
+            r#"date = "2018-09-01"
+[pkg.d-dead.target.lol]
+available = true
+"#,
+        ];
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifests(manifests.iter().map(|data| toml::from_str(data).unwrap()));
+        let dates = vec![
+            NaiveDate::from_ymd_opt(2018, 9, 3).unwrap(),
+            NaiveDate::from_ymd_opt(2018, 9, 2).unwrap(),
+        ];
+        let names = |order: SortOrder| -> Vec<String> {
+            Table::builder(&availability, "lol")
+                .dates(&dates)
+                .sort_by(order)
+                .build()
+                .packages_availability
+                .iter()
+                .map(|row| row.package_name.to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            names(SortOrder::Name),
+            vec!["a-stable", "b-flaky", "c-broken", "d-dead"]
+        );
+        assert_eq!(
+            names(SortOrder::LastAvailable),
+            vec!["d-dead", "c-broken", "a-stable", "b-flaky"]
+        );
+        assert_eq!(
+            names(SortOrder::CurrentAvailability),
+            vec!["c-broken", "d-dead", "a-stable", "b-flaky"]
+        );
+        assert_eq!(
+            names(SortOrder::AvailabilityRatio),
+            vec!["d-dead", "b-flaky", "c-broken", "a-stable"]
+        );
+        assert_eq!(
+            names(SortOrder::Priority(vec![
+                "c-broken".into(),
+                "a-stable".into()
+            ])),
+            vec!["c-broken", "a-stable", "b-flaky", "d-dead"]
+        );
+    }
+}
@@ -1,70 +1,125 @@
-//! Types that are used internally.
+//! Target triples parsing.
 
-use std::fmt;
-use std::{borrow::Borrow, ops::Deref, rc::Rc};
+/// Operating systems that may appear right after the architecture in target triples that omit the
+/// vendor, like `aarch64-linux-android` or `wasm32-wasip1-threads`.
+const VENDORLESS_OSES: &[&str] = &["linux", "none", "windows", "freebsd", "netbsd", "openbsd"];
 
-/// Reference-counted build-target triple.
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub struct TargetTriple(Rc<str>);
+/// Environments that may end a target triple that omits the vendor, like `armv7a-nuttx-eabihf`.
+const VENDORLESS_ENVS: &[&str] = &["eabi", "eabihf", "elf", "android", "androideabi"];
 
-impl fmt::Display for TargetTriple {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
+/// Components of a target triple, e.g. `x86_64` (architecture), `unknown` (vendor), `linux`
+/// (operating system) and `gnu` (environment) for `x86_64-unknown-linux-gnu`.
+///
+/// Target "triples" are notoriously irregular, so parsing is lenient and never fails: any
+/// components that can't be recognized are left empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TargetComponents<'a> {
+    /// Architecture, like `x86_64`, `aarch64` or `wasm32`.
+    pub arch: &'a str,
+    /// Vendor, like `unknown`, `pc` or `apple`.
+    pub vendor: Option<&'a str>,
+    /// Operating system, like `linux`, `windows` or `darwin`.
+    pub os: Option<&'a str>,
+    /// Environment (or ABI), like `gnu`, `msvc` or `gnullvm`. Everything after the operating
+    /// system is considered to be the environment.
+    pub env: Option<&'a str>,
 }
 
-impl Deref for TargetTriple {
-    type Target = str;
-
-    fn deref(&self) -> &str {
-        &self.0
+impl<'a> TargetComponents<'a> {
+    /// Splits a target triple into components.
+    pub fn parse(triple: &'a str) -> Self {
+        let (arch, rest) = split_first(triple);
+        let (vendor, rest) = match rest.and_then(|rest| rest.split_once('-')) {
+            Some((first, tail)) if !is_vendorless(first, tail) => (Some(first), Some(tail)),
+            _ => (None, rest),
+        };
+        let (os, env) = match rest.map(split_first) {
+            Some((os, env)) => (Some(os), env),
+            None => (None, None),
+        };
+        TargetComponents {
+            arch,
+            vendor,
+            os,
+            env,
+        }
     }
 }
 
-impl Borrow<str> for TargetTriple {
-    fn borrow(&self) -> &str {
-        &self.0
-    }
+/// Checks whether a triple omits the vendor, given the component that follows the architecture
+/// and everything after it.
+fn is_vendorless(first: &str, tail: &str) -> bool {
+    VENDORLESS_OSES.contains(&first) || first.starts_with("wasi") || VENDORLESS_ENVS.contains(&tail)
 }
 
-impl<T> From<T> for TargetTriple
-where
-    Rc<str>: From<T>,
-{
-    fn from(t: T) -> Self {
-        TargetTriple(t.into())
+/// Splits a string at the first dash.
+fn split_first(s: &str) -> (&str, Option<&str>) {
+    match s.split_once('-') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (s, None),
     }
 }
 
-/// Reference-counted package name.
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub struct PackageName(Rc<str>);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Deref for PackageName {
-    type Target = str;
-
-    fn deref(&self) -> &str {
-        &self.0
+    fn parse(triple: &str) -> (&str, Option<&str>, Option<&str>, Option<&str>) {
+        let components = TargetComponents::parse(triple);
+        (
+            components.arch,
+            components.vendor,
+            components.os,
+            components.env,
+        )
     }
-}
-
-impl AsRef<str> for PackageName {
-    fn as_ref(&self) -> &str {
-        &self.0
-    }
-}
-
-impl Borrow<str> for PackageName {
-    fn borrow(&self) -> &str {
-        &self.0
-    }
-}
 
-impl<T> From<T> for PackageName
-where
-    Rc<str>: From<T>,
-{
-    fn from(t: T) -> Self {
-        PackageName(t.into())
+    #[test]
+    fn components() {
+        assert_eq!(
+            parse("x86_64-unknown-linux-gnu"),
+            ("x86_64", Some("unknown"), Some("linux"), Some("gnu"))
+        );
+        assert_eq!(
+            parse("x86_64-pc-windows-gnullvm"),
+            ("x86_64", Some("pc"), Some("windows"), Some("gnullvm"))
+        );
+        assert_eq!(
+            parse("wasm32-unknown-unknown"),
+            ("wasm32", Some("unknown"), Some("unknown"), None)
+        );
+        assert_eq!(
+            parse("x86_64-apple-darwin"),
+            ("x86_64", Some("apple"), Some("darwin"), None)
+        );
+        assert_eq!(
+            parse("aarch64-linux-android"),
+            ("aarch64", None, Some("linux"), Some("android"))
+        );
+        assert_eq!(
+            parse("wasm32-wasip1"),
+            ("wasm32", None, Some("wasip1"), None)
+        );
+        assert_eq!(
+            parse("wasm32-wasip1-threads"),
+            ("wasm32", None, Some("wasip1"), Some("threads"))
+        );
+        assert_eq!(
+            parse("aarch64-apple-ios-macabi"),
+            ("aarch64", Some("apple"), Some("ios"), Some("macabi"))
+        );
+        assert_eq!(
+            parse("x86_64-unknown-linux-gnu-extra"),
+            ("x86_64", Some("unknown"), Some("linux"), Some("gnu-extra"))
+        );
+        assert_eq!(
+            parse("armv7a-nuttx-eabihf"),
+            ("armv7a", None, Some("nuttx"), Some("eabihf"))
+        );
+        assert_eq!(
+            parse("armv7a-vex-v5"),
+            ("armv7a", Some("vex"), Some("v5"), None)
+        );
+        assert_eq!(parse("*"), ("*", None, None, None));
     }
 }
@@ -0,0 +1,195 @@
+use chrono::NaiveDate;
+use rustup_available_packages::availability::AvailabilityRow;
+
+use crate::opts::BadgeStyle;
+
+/// Approximate width of a single character in the badge font (Verdana, 11px).
+const CHAR_WIDTH: usize = 7;
+
+/// Horizontal padding on each side of a badge part.
+const PADDING: usize = 6;
+
+const LABEL_COLOR: &str = "#555";
+const AVAILABLE_COLOR: &str = "#4c1";
+const MISSING_COLOR: &str = "#e05d44";
+const UNKNOWN_COLOR: &str = "#9f9f9f";
+
+/// A status badge of a package on a target.
+pub struct Badge {
+    label: String,
+    message: String,
+    color: &'static str,
+}
+
+impl Badge {
+    /// Prepares a badge for a given availability row.
+    ///
+    /// The first entry of the availability list is treated as the current status, since the dates
+    /// are sorted in a descending order.
+    pub fn new(target: &str, row: &AvailabilityRow) -> Self {
+        let label = format!("{} on {}", row.package_name, target);
+        let (message, color) = status(row.availability_list.first(), row.last_available);
+        Badge {
+            label,
+            message,
+            color,
+        }
+    }
+
+    /// Renders the badge as an SVG image.
+    pub fn render(&self, style: BadgeStyle) -> String {
+        let label_width = text_width(&self.label);
+        let message_width = text_width(&self.message);
+        let width = label_width + message_width;
+        let label = escape(&self.label);
+        let message = escape(&self.message);
+        let (radius, gradient, shade) = match style {
+            BadgeStyle::Flat => (
+                3,
+                r##"<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>"##,
+                format!(r#"<rect width="{}" height="20" fill="url(#s)"/>"#, width),
+            ),
+            BadgeStyle::FlatSquare => (0, "", String::new()),
+        };
+        format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">"#,
+                "<title>{label}: {message}</title>",
+                "{gradient}",
+                r##"<clipPath id="r"><rect width="{width}" height="20" rx="{radius}" fill="#fff"/></clipPath>"##,
+                r#"<g clip-path="url(#r)">"#,
+                r#"<rect width="{label_width}" height="20" fill="{label_color}"/>"#,
+                r#"<rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>"#,
+                "{shade}</g>",
+                r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">"##,
+                r#"<text x="{label_x}" y="14">{label}</text>"#,
+                r#"<text x="{message_x}" y="14">{message}</text>"#,
+                "</g></svg>\n",
+            ),
+            width = width,
+            label = label,
+            message = message,
+            gradient = gradient,
+            radius = radius,
+            label_width = label_width,
+            label_color = LABEL_COLOR,
+            message_width = message_width,
+            color = self.color,
+            shade = shade,
+            label_x = label_width / 2,
+            message_x = label_width + message_width / 2,
+        )
+    }
+}
+
+/// Makes a message and a color of a badge given the current availability and the date when a
+/// package was last available.
+fn status(current: Option<&bool>, last_available: Option<NaiveDate>) -> (String, &'static str) {
+    let last_available = last_available.map(|date| date.format("%Y-%m-%d"));
+    match (current, last_available) {
+        (Some(true), Some(date)) => (format!("available ({})", date), AVAILABLE_COLOR),
+        (_, Some(date)) => (format!("missing (last: {})", date), MISSING_COLOR),
+        (_, None) => ("missing".to_string(), UNKNOWN_COLOR),
+    }
+}
+
+/// Estimates a width of a badge part containing a given text.
+fn text_width(text: &str) -> usize {
+    text.chars().count() * CHAR_WIDTH + 2 * PADDING
+}
+
+/// Escapes a text to be safely put into an XML document.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustup_available_packages::{manifest::Manifest, AvailabilityData};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2019, 3, day).unwrap()
+    }
+
+    /// Makes a manifest where `pkg` is available on `x86_64-unknown-linux-gnu` or not.
+    fn manifest(day: u32, available: bool) -> Manifest {
+        serde_json::from_value(serde_json::json!({
+            "date": date(day),
+            "pkg": {
+                "pkg": {
+                    "target": {
+                        "x86_64-unknown-linux-gnu": { "available": available },
+                    },
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn new() {
+        let target = "x86_64-unknown-linux-gnu";
+        let mut data = AvailabilityData::default();
+        data.add_manifests(vec![manifest(1, true), manifest(2, false)]);
+
+        let row = data.get_availability_row(target, "pkg", [date(1)]).unwrap();
+        let badge = Badge::new(target, &row);
+        assert_eq!("pkg on x86_64-unknown-linux-gnu", badge.label);
+        assert_eq!("available (2019-03-01)", badge.message);
+        assert_eq!(AVAILABLE_COLOR, badge.color);
+
+        let row = data
+            .get_availability_row(target, "pkg", [date(2), date(1)])
+            .unwrap();
+        let badge = Badge::new(target, &row);
+        assert_eq!("missing (last: 2019-03-01)", badge.message);
+        assert_eq!(MISSING_COLOR, badge.color);
+
+        // A row of a never available package can't be got from the data.
+        assert_eq!(("missing".to_string(), UNKNOWN_COLOR), status(None, None));
+        assert_eq!(
+            ("missing".to_string(), UNKNOWN_COLOR),
+            status(Some(&false), None)
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!("a &amp;&lt;&gt;&quot;&apos; b", escape("a &<>\"' b"));
+    }
+
+    #[test]
+    fn render() {
+        let badge = Badge {
+            label: "<pkg> on target".to_string(),
+            message: "missing".to_string(),
+            color: UNKNOWN_COLOR,
+        };
+        let flat = badge.render(BadgeStyle::Flat);
+        assert!(flat.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="178" "#));
+        assert!(flat.contains("<title>&lt;pkg&gt; on target: missing</title>"));
+        assert!(flat.contains("<linearGradient"));
+        assert!(flat.contains(r#"fill="url(#s)""#));
+        assert!(flat.contains(r##"<rect width="178" height="20" rx="3" fill="#fff"/>"##));
+        assert!(flat.contains(r##"<rect x="117" width="61" height="20" fill="#9f9f9f"/>"##));
+        assert!(flat.contains(r#"<text x="58" y="14">&lt;pkg&gt; on target</text>"#));
+        assert!(flat.ends_with("</svg>\n"));
+
+        let square = badge.render(BadgeStyle::FlatSquare);
+        assert!(!square.contains("<linearGradient"));
+        assert!(!square.contains(r#"fill="url(#s)""#));
+        assert!(square.contains(r##"<rect width="178" height="20" rx="0" fill="#fff"/>"##));
+    }
+}
@@ -1,88 +1,110 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::Path};
 
 use anyhow::Context;
 use log::LevelFilter;
-use strum::IntoEnumIterator;
-use tl::{ParserOptions, VDom};
+use rustup_available_packages::cache::Compression;
 
-use crate::opts::{Config, Html, Tier};
+use crate::{
+    opts::{
+        BadgeStyle, Badges, Config, Html, MissingManifests, Navigation, Packages, TargetInfo,
+        Targets, Tier,
+    },
+    platform_support::{self, PlatformTarget},
+};
 
-pub fn gen_config() -> anyhow::Result<Config> {
+const PLATFORM_SUPPORT_URL: &str = "https://doc.rust-lang.org/nightly/rustc/platform-support.html";
+
+pub fn gen_config(platform_support: Option<&Path>) -> anyhow::Result<Config> {
+    let (tiers, target_info) = gen_tiers(platform_support);
     Ok(Config {
         html: Html {
             template_path: "template.html".into(),
             output_pattern: "output/{{target}}.html".into(),
-            tiers: gen_tiers()?,
+            tiers,
+            target_info,
+            navigation: Navigation::Tiers,
         },
         days_in_past: 7,
         additional_lookup_days: 22,
         channel: "nightly".into(),
+        base_urls: Vec::new(),
         verbosity: LevelFilter::Info,
         cache_path: Some("/tmp/manifests/".into()),
+        cache_retention: Default::default(),
+        cache_compression: Compression::Zstd,
         file_tree_output: "output/".into(),
+        badges: Badges {
+            enabled: true,
+            style: BadgeStyle::Flat,
+        },
+        packages: Packages {
+            filter: Default::default(),
+            hide_unseen: false,
+            sort: Default::default(),
+            priority: Vec::new(),
+        },
+        targets: Targets::default(),
+        missing_manifests: MissingManifests::default(),
     })
 }
 
-fn gen_tiers() -> anyhow::Result<HashMap<Tier, Vec<String>>> {
-    let bytes =
-        reqwest::blocking::get("https://doc.rust-lang.org/nightly/rustc/platform-support.html")?
-            .text()?;
-    let html = tl::parse(&bytes, ParserOptions::default())?;
+type Tiers = (HashMap<Tier, Vec<String>>, HashMap<String, TargetInfo>);
 
-    let mut tiers = HashMap::new();
-    for tier in Tier::iter().filter(|t| *t != Tier::UnknownTier) {
-        tiers.insert(tier, collect_targets_for_tier(&html, tier)?);
+/// Collects platform tiers from a local copy of the platform support page if provided, or from
+/// the online one otherwise.
+///
+/// Every fallback is logged as a warning. If neither source is available, no tiers are listed, so
+/// all the targets will be rendered as targets of an unknown tier.
+fn gen_tiers(platform_support: Option<&Path>) -> Tiers {
+    if let Some(path) = platform_support {
+        match load_tiers(path) {
+            Ok(tiers) => return tiers,
+            Err(e) => log::warn!(
+                "Can't collect tiers from {:?}, falling back to {}: {:#}",
+                path,
+                PLATFORM_SUPPORT_URL,
+                e
+            ),
+        }
+    }
+    match fetch_tiers() {
+        Ok(tiers) => tiers,
+        Err(e) => {
+            log::warn!(
+                "Can't collect tiers from {}, no tiers will be listed: {:#}",
+                PLATFORM_SUPPORT_URL,
+                e
+            );
+            Default::default()
+        }
     }
-    Ok(tiers)
 }
 
-fn collect_targets_for_tier(html: &VDom, tier: Tier) -> anyhow::Result<Vec<String>> {
-    let mut targets = Vec::new();
-
-    for table_row in html
-        .query_selector("tbody")
-        .unwrap()
-        .nth(match tier {
-            Tier::Tier1 => 0,
-            Tier::Tier2 => 1,
-            Tier::Tier25 => 2,
-            Tier::Tier3 => 3,
-            Tier::UnknownTier => unreachable!(),
-        })
-        .context("Unexpected tier table layout")?
-        .get(html.parser())
-        .unwrap()
-        .children()
-        .context("tbody is not a tag")?
-        .top()
-        .iter()
-    {
-        if let Some(table_row) = table_row.get(html.parser()).unwrap().as_tag() {
-            if table_row.name() != "tr" {
-                continue;
-            }
+fn fetch_tiers() -> anyhow::Result<Tiers> {
+    let bytes = reqwest::blocking::get(PLATFORM_SUPPORT_URL)?.text()?;
+    let targets = platform_support::parse_html(&bytes)
+        .with_context(|| format!("Can't parse {}", PLATFORM_SUPPORT_URL))?;
+    Ok(group_by_tiers(targets))
+}
 
-            targets.push(
-                table_row
-                    .query_selector(html.parser(), "td")
-                    .unwrap()
-                    .next()
-                    .context("Table row does not have any columns.")?
-                    .get(html.parser())
-                    .unwrap()
-                    .as_tag()
-                    .context("td is not a tag")?
-                    .query_selector(html.parser(), "code")
-                    .unwrap()
-                    .next()
-                    .context("Table row does not have a code element in its first column")?
-                    .get(html.parser())
-                    .unwrap()
-                    .inner_text(html.parser())
-                    .into_owned(),
-            );
-        }
+/// Loads tiers from a local copy of the platform support page, which is either an HTML page or a
+/// markdown source (`src/doc/rustc/src/platform-support.md` in the rust-lang/rust repository).
+fn load_tiers(path: &Path) -> anyhow::Result<Tiers> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Can't read {:?}", path))?;
+    let targets = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("md") => platform_support::parse_markdown(&contents),
+        _ => platform_support::parse_html(&contents),
     }
+    .with_context(|| format!("Can't parse {:?}", path))?;
+    Ok(group_by_tiers(targets))
+}
 
-    Ok(targets)
+fn group_by_tiers(targets: Vec<PlatformTarget>) -> Tiers {
+    let mut tiers: HashMap<Tier, Vec<String>> = HashMap::new();
+    let mut target_info = HashMap::new();
+    for PlatformTarget { name, tier, info } in targets {
+        tiers.entry(tier).or_default().push(name.clone());
+        target_info.insert(name, info);
+    }
+    (tiers, target_info)
 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
     fs::{create_dir_all, File},
     io::{self, Write},
@@ -7,28 +7,86 @@ use std::{
 };
 
 use anyhow::Context;
+use badges::Badge;
 use chrono::{NaiveDate, Utc};
 use handlebars::{handlebars_helper, Handlebars};
+use log::LevelFilter;
 use opts::Config;
-use rustup_available_packages::{cache::FsCache, table::Table, AvailabilityData, Downloader};
+use progress::ProgressReporter;
+use rustup_available_packages::{
+    availability::AvailabilityStats,
+    cache::{FsCache, RetentionPolicy},
+    table::{ExportFormat, Table},
+    AvailabilityData, DefaultSource, Downloader,
+};
 use serde::Serialize;
 use structopt::StructOpt;
-use tiers_table::TiersTable;
+use tiers_table::{CurrentTarget, TiersTable};
 
+mod badges;
 mod config_gen;
 mod opts;
+mod platform_support;
+mod progress;
 mod tiers_table;
 
 #[derive(StructOpt)]
 #[structopt(about = "Rust tools per-release availability monitor")]
 enum CmdOpts {
     #[structopt(name = "render", about = "Renders pages using provided configuration")]
-    Render(ConfigOpt),
+    Render(DataOpt),
     #[structopt(
         name = "print_config",
         about = "Prints the default configuration to stdout"
     )]
     PrintConfig(PrintConfig),
+    #[structopt(
+        name = "export",
+        about = "Exports packages availability on a target as a plain-text table"
+    )]
+    Export(Export),
+    #[structopt(
+        name = "fetch",
+        about = "Downloads manifests for a range of dates into the cache, skipping \
+                 the already cached ones"
+    )]
+    Fetch(Fetch),
+    #[structopt(name = "cache", about = "Manages the manifests cache")]
+    Cache(CacheCmd),
+}
+
+#[derive(StructOpt)]
+enum CacheCmd {
+    #[structopt(
+        name = "prune",
+        about = "Removes cached manifests according to the retention policy. \
+                 The command line limits override the ones from the configuration"
+    )]
+    Prune(Prune),
+    #[structopt(
+        name = "reparse",
+        about = "Parses all the cached manifests again and reports the ones that fail"
+    )]
+    Reparse(ConfigOpt),
+    #[structopt(
+        name = "verify",
+        about = "Checks the cached manifests for corruption and date mismatches"
+    )]
+    Verify(Verify),
+}
+
+#[derive(StructOpt)]
+struct Verify {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(long = "delete", help = "Delete the broken entries")]
+    delete: bool,
+    #[structopt(
+        long = "redownload",
+        help = "Download the broken entries again",
+        conflicts_with = "delete"
+    )]
+    redownload: bool,
 }
 
 #[derive(StructOpt)]
@@ -42,6 +100,18 @@ struct ConfigOpt {
     config_path: PathBuf,
 }
 
+#[derive(StructOpt)]
+struct DataOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(
+        long = "offline",
+        help = "Only read manifests from the cache, never touching the network. \
+                The most recent cached manifest is treated as the latest one"
+    )]
+    offline: bool,
+}
+
 #[derive(StructOpt)]
 struct PrintConfig {
     #[structopt(
@@ -51,6 +121,81 @@ struct PrintConfig {
         parse(from_os_str)
     )]
     config_path: Option<PathBuf>,
+    #[structopt(
+        long = "platform-support",
+        help = "Path to a local copy of the platform support page, either an HTML page \
+                (platform-support.html) or its markdown source (platform-support.md). \
+                If omitted or unusable, the page is fetched from doc.rust-lang.org, \
+                and if that fails too, no tiers are listed",
+        parse(from_os_str)
+    )]
+    platform_support: Option<PathBuf>,
+}
+
+#[derive(StructOpt)]
+struct Export {
+    #[structopt(flatten)]
+    data: DataOpt,
+    #[structopt(short = "t", long = "target", help = "A target to export")]
+    target: String,
+    #[structopt(
+        short = "f",
+        long = "format",
+        help = "Output format",
+        default_value = "txt",
+        possible_values = &["csv", "md", "txt"],
+        parse(try_from_str = parse_export_format)
+    )]
+    format: ExportFormat,
+    #[structopt(
+        short = "o",
+        long = "output",
+        help = "Path to an output file. If omitted, the table is printed to stdout",
+        parse(from_os_str)
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(StructOpt)]
+struct Fetch {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(long = "from", help = "The first date to fetch, e.g. 2019-12-24")]
+    from: NaiveDate,
+    #[structopt(
+        long = "to",
+        help = "The last date to fetch, e.g. 2019-12-31. If omitted, today is used"
+    )]
+    to: Option<NaiveDate>,
+}
+
+#[derive(StructOpt)]
+struct Prune {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(long = "keep-days", help = "Number of most recent days to keep")]
+    keep_days: Option<usize>,
+    #[structopt(long = "max-size", help = "Maximum total size of the cache in bytes")]
+    max_size: Option<u64>,
+    #[structopt(
+        long = "max-age-days",
+        help = "Maximum age of cached manifests in days"
+    )]
+    max_age_days: Option<u32>,
+    #[structopt(
+        long = "dry-run",
+        help = "Only print what would be removed, without removing anything"
+    )]
+    dry_run: bool,
+}
+
+fn parse_export_format(format: &str) -> anyhow::Result<ExportFormat> {
+    match format {
+        "csv" => Ok(ExportFormat::Csv),
+        "md" => Ok(ExportFormat::Markdown),
+        "txt" => Ok(ExportFormat::Ascii),
+        _ => anyhow::bail!("Unknown format {}", format),
+    }
 }
 
 #[derive(Serialize)]
@@ -64,14 +209,27 @@ struct TiersData<'a> {
     datetime: String,
 }
 
+#[derive(Serialize)]
+struct PageData<'a> {
+    #[serde(flatten)]
+    tiers: &'a TiersData<'a>,
+    /// Tier and details of the target the page is rendered for, if it's listed
+    /// in any tier.
+    current: Option<CurrentTarget<'a>>,
+}
+
 fn generate_html(
     data: &AvailabilityData,
+    targets: &HashSet<&str>,
     dates: &[NaiveDate],
     opts::Html {
         template_path,
         output_pattern,
         tiers,
+        target_info,
+        navigation,
     }: opts::Html,
+    packages: &opts::Packages,
 ) -> anyhow::Result<()> {
     const TEMPLATE_NAME: &str = "target_info";
     let mut handlebars = Handlebars::new();
@@ -82,14 +240,12 @@ fn generate_html(
         .register_template_file(TEMPLATE_NAME, &template_path)
         .with_context(|| format!("File path: {:?}", &template_path))?;
 
-    let all_targets = data.get_available_targets();
-
-    let additional = TiersData {
-        tiers: TiersTable::new(tiers, &all_targets),
+    let tiers_data = TiersData {
+        tiers: TiersTable::new(tiers, &target_info, targets, navigation),
         datetime: Utc::now().format("%d %b %Y, %H:%M:%S UTC").to_string(),
     };
 
-    for target in &all_targets {
+    for target in targets {
         log::info!("Processing target {}", target);
         let output_path = handlebars
             .render_template(&output_pattern, &PathRenderData { target })
@@ -102,9 +258,15 @@ fn generate_html(
         let out = File::create(&output_path)
             .with_context(|| format!("Can't create file [{}]", output_path))?;
 
-        let table = Table::builder(&data, target)
+        let table = Table::builder(data, target)
             .dates(dates)
-            .additional(&additional)
+            .packages(packages.filter.clone())
+            .hide_unseen(packages.hide_unseen)
+            .sort_by(packages.sort_order())
+            .additional(PageData {
+                tiers: &tiers_data,
+                current: tiers_data.tiers.find(target),
+            })
             .build();
 
         log::info!("Writing target {} to {:?}", target, output_path);
@@ -131,10 +293,11 @@ fn packages_json(
 
 fn generate_fs_tree(
     data: &AvailabilityData,
+    targets: &HashSet<&str>,
     dates: &[NaiveDate],
     output: &Path,
+    badges: &opts::Badges,
 ) -> anyhow::Result<()> {
-    let targets = data.get_available_targets();
     let pkgs = data.get_available_packages();
 
     packages_json(&pkgs, output.join("packages.json")).with_context(|| "packages.json")?;
@@ -163,7 +326,7 @@ fn generate_fs_tree(
             // This should always be true, but better to output nothing than to panic
             // or output corrupt data.
             if dates.len() == row.availability_list.len() {
-                let path = target_path.join(&format!("{}.json", pkg));
+                let path = target_path.join(format!("{}.json", pkg));
 
                 let contents = serde_json::to_vec_pretty(&TargetPkg {
                     availability: dates
@@ -172,9 +335,16 @@ fn generate_fs_tree(
                         .map(|(date, avail)| (date.format("%Y-%m-%d").to_string(), *avail))
                         .collect(),
                     last_available: row.last_available.map(|d| d.format("%Y-%m-%d").to_string()),
+                    stats: data.get_availability_stats(target, pkg, dates),
                 })?;
                 std::fs::write(&path, contents)
                     .with_context(|| format!("Can't write file {}", path.display()))?;
+
+                if badges.enabled {
+                    let path = target_path.join(format!("{}.svg", pkg));
+                    std::fs::write(&path, Badge::new(target, &row).render(badges.style))
+                        .with_context(|| format!("Can't write file {}", path.display()))?;
+                }
             }
         }
     }
@@ -186,65 +356,368 @@ struct TargetPkg {
     #[serde(flatten)]
     availability: HashMap<String, bool>,
     last_available: Option<String>,
+    stats: Option<AvailabilityStats>,
 }
 
-fn main() -> anyhow::Result<()> {
-    let cmd_opts = CmdOpts::from_args();
-    let config = match cmd_opts {
-        CmdOpts::Render(cmd_opts) => Config::load(&cmd_opts.config_path)
-            .with_context(|| format!("Can't load config {:?}", cmd_opts.config_path))?,
-        CmdOpts::PrintConfig(PrintConfig { config_path }) => {
-            const HEADER: &str = "\
+fn print_config(
+    PrintConfig {
+        config_path,
+        platform_support,
+    }: PrintConfig,
+) -> anyhow::Result<()> {
+    const HEADER: &str = "\
 # DO NOT EDIT MANUALLY
 #
 # This file was auto-generated by the print_config command:
 # $ cargo r -p rustup-available-packages-web -- print_config -c config.yaml
 ";
 
-            let config = config_gen::gen_config()?;
-            if let Some(path) = config_path {
-                if let Some(parent) = path.parent() {
-                    create_dir_all(parent)?;
-                }
-
-                let f = File::create(&path)?;
-                writeln!(&f, "{HEADER}")?;
-                serde_yaml::to_writer(&f, &config)
-                    .with_context(|| format!("Failed to write config {path:?}"))?;
-            } else {
-                let mut out = io::stdout().lock();
-                writeln!(out, "{HEADER}")?;
-                serde_yaml::to_writer(out, &config)?;
-            }
-            return Ok(());
-        }
-    };
     env_logger::Builder::from_default_env()
-        .filter_level(config.verbosity)
+        .filter_level(LevelFilter::Warn)
         .init();
 
-    let mut data: AvailabilityData = Default::default();
-    let cache = if let Some(cache_path) = config.cache_path.as_ref() {
-        FsCache::new(cache_path).with_context(|| "Can't initialize cache")?
+    let config = config_gen::gen_config(platform_support.as_deref())?;
+    if let Some(path) = config_path {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let f = File::create(&path)?;
+        writeln!(&f, "{HEADER}")?;
+        serde_yaml::to_writer(&f, &config)
+            .with_context(|| format!("Failed to write config {path:?}"))?;
+    } else {
+        let mut out = io::stdout().lock();
+        writeln!(out, "{HEADER}")?;
+        serde_yaml::to_writer(out, &config)?;
+    }
+    Ok(())
+}
+
+/// Loads a configuration file and initializes the logger accordingly.
+fn load_config(ConfigOpt { config_path }: &ConfigOpt) -> anyhow::Result<Config> {
+    let config = Config::load(config_path)
+        .with_context(|| format!("Can't load config {:?}", config_path))?;
+    progress::init_logger(env_logger::Builder::from_default_env().filter_level(config.verbosity));
+    Ok(config)
+}
+
+/// Creates a downloader for the configured channel and base URLs.
+fn make_downloader(config: &Config) -> Downloader<DefaultSource<'_>> {
+    let mut source = DefaultSource::new(&config.channel);
+    let mut base_urls = config.base_urls.iter();
+    if let Some(base_url) = base_urls.next() {
+        source.override_base(base_url.into());
+    }
+    for base_url in base_urls {
+        source.add_fallback_base(base_url.into());
+    }
+    Downloader::new(source)
+}
+
+/// Opens the cache configured in the config, if any.
+fn open_cache(config: &Config) -> anyhow::Result<FsCache> {
+    Ok(if let Some(cache_path) = config.cache_path.as_ref() {
+        FsCache::new(cache_path)
+            .with_context(|| "Can't initialize cache")?
+            .set_compression(config.cache_compression)
     } else {
         FsCache::noop()
-    };
-    let downloader = Downloader::with_default_source(&config.channel)
-        .set_cache(cache)
-        .skip_missing_days(7);
-    let manifests =
-        downloader.get_last_manifests(config.days_in_past + config.additional_lookup_days)?;
-    let dates: Vec<_> = manifests
-        .iter()
-        .map(|manifest| manifest.date)
-        .take(config.days_in_past)
-        .collect();
-    data.add_manifests(manifests);
+    })
+}
+
+/// Downloads the manifests and collects the availability data and the dates to be displayed.
+fn load_data(config: &Config, offline: bool) -> anyhow::Result<(AvailabilityData, Vec<NaiveDate>)> {
+    if offline && config.cache_path.is_none() {
+        anyhow::bail!("The offline mode requires a cache, but no cache_path is configured");
+    }
+    let mut data: AvailabilityData = Default::default();
+    let days = config.days_in_past + config.additional_lookup_days;
+    let progress = ProgressReporter::new(days);
+    let downloader = make_downloader(config)
+        .set_cache(open_cache(config)?)
+        .missing_policy(
+            config
+                .missing_manifests
+                .policy()
+                .with_context(|| "Invalid missing_manifests configuration")?,
+        )
+        .offline(offline)
+        .progress_observer(progress.clone());
+    // Manifests are added one by one, so only one of them is kept in memory at a time.
+    let mut manifests = downloader.iter_last_manifests(days)?;
+    let mut dates = Vec::new();
+    data.try_add_manifests(manifests.by_ref().inspect(|manifest| {
+        if let Ok(manifest) = manifest {
+            dates.push(manifest.date);
+        }
+    }))?;
+    progress.finish();
+    dates.truncate(config.days_in_past);
+    let skipped = manifests.skipped();
+    if !skipped.is_empty() {
+        let skipped: Vec<_> = skipped.iter().map(|day| day.date.to_string()).collect();
+        log::warn!("Skipped days without manifests: {}", skipped.join(", "));
+    }
     log::info!("Available targets: {:?}", data.get_available_targets());
     log::info!("Available packages: {:?}", data.get_available_packages());
+    Ok((data, dates))
+}
+
+fn render(config: Config, offline: bool) -> anyhow::Result<()> {
+    let (data, dates) = load_data(&config, offline)?;
+
+    if !config.cache_retention.is_empty() {
+        let removed = open_cache(&config)?
+            .prune(&config.cache_retention, false)
+            .with_context(|| "Can't prune cache")?;
+        log::info!("Removed {} cached manifests", removed.len());
+    }
+
+    let targets = config
+        .targets
+        .select(data.get_available_targets(), &config.html.tiers);
+    log::info!("Selected targets: {:?}", targets);
+
+    generate_html(&data, &targets, &dates, config.html, &config.packages)?;
+    generate_fs_tree(
+        &data,
+        &targets,
+        &dates,
+        &config.file_tree_output,
+        &config.badges,
+    )?;
+
+    Ok(())
+}
+
+fn export(config: Config, opts: Export) -> anyhow::Result<()> {
+    let (data, dates) = load_data(&config, opts.data.offline)?;
+    if !data.get_available_targets().contains(opts.target.as_str()) {
+        anyhow::bail!("Target {} is not found in the manifests", opts.target);
+    }
+
+    let table = Table::builder(&data, &opts.target)
+        .dates(&dates)
+        .first_cell(&opts.target)
+        .packages(config.packages.filter.clone())
+        .hide_unseen(config.packages.hide_unseen)
+        .sort_by(config.packages.sort_order())
+        .build();
+    if let Some(path) = &opts.output {
+        let mut f =
+            File::create(path).with_context(|| format!("Can't create file {}", path.display()))?;
+        table
+            .export(opts.format, &mut f)
+            .with_context(|| format!("Can't write file {}", path.display()))?;
+    } else {
+        table.export(opts.format, &mut io::stdout().lock())?;
+    }
+    Ok(())
+}
+
+fn fetch(config: Config, opts: Fetch) -> anyhow::Result<()> {
+    let to = opts.to.unwrap_or_else(|| Utc::now().naive_utc().date());
+    if opts.from > to {
+        anyhow::bail!("The range is empty: {} is after {}", opts.from, to);
+    }
+    if config.cache_path.is_none() {
+        anyhow::bail!("No cache is configured, there is nowhere to fetch to");
+    }
+    let policy = config
+        .missing_manifests
+        .policy()
+        .with_context(|| "Invalid missing_manifests configuration")?;
+    let cache = open_cache(&config)?;
+    let progress = ProgressReporter::new((to - opts.from).num_days() as usize + 1);
+    let downloader = make_downloader(&config)
+        .set_cache(open_cache(&config)?)
+        .progress_observer(progress.clone());
+
+    let (mut fetched, mut cached, mut missing) = (0, 0, Vec::new());
+    let mut failed = Vec::new();
+    for date in opts.from.iter_days().take_while(|date| *date <= to) {
+        if cache.contains(date) {
+            log::debug!("Manifest for {} is already cached", date);
+            progress.skip_day();
+            cached += 1;
+            continue;
+        }
+        match downloader.get_manifest(date) {
+            Ok(_) => fetched += 1,
+            // The skip budget doesn't matter here, since all the days are tried anyway.
+            Err(e) if policy.is_missing(&e) => {
+                log::info!("No manifest for {}: {}", date, e);
+                missing.push(date);
+            }
+            Err(e) => {
+                log::error!("Can't fetch a manifest for {}: {}", date, e);
+                failed.push(date);
+            }
+        }
+    }
+    progress.finish();
+
+    println!("Fetched: {}", fetched);
+    println!("Already cached: {}", cached);
+    println!("Missing on the server: {}", missing.len());
+    for date in &missing {
+        println!("  {}", date);
+    }
+    println!("Failed: {}", failed.len());
+    for date in &failed {
+        println!("  {}", date);
+    }
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "{} manifests failed to download, run the command again to retry",
+            failed.len()
+        );
+    }
+    Ok(())
+}
 
-    generate_html(&data, &dates, config.html)?;
-    generate_fs_tree(&data, &dates, &config.file_tree_output)?;
+fn prune(config: Config, opts: Prune) -> anyhow::Result<()> {
+    let cache_path = config
+        .cache_path
+        .as_ref()
+        .with_context(|| "No cache is configured")?;
+    let defaults = &config.cache_retention;
+    let policy = RetentionPolicy {
+        keep_days: opts.keep_days.or(defaults.keep_days),
+        max_size: opts.max_size.or(defaults.max_size),
+        max_age_days: opts.max_age_days.or(defaults.max_age_days),
+    };
+    if policy.is_empty() {
+        anyhow::bail!("No retention limits are set, nothing to prune");
+    }
 
+    let cache = FsCache::new(cache_path).with_context(|| "Can't initialize cache")?;
+    let removed = cache
+        .prune(&policy, opts.dry_run)
+        .with_context(|| format!("Can't prune cache {}", cache_path.display()))?;
+    let action = if opts.dry_run {
+        "Would remove"
+    } else {
+        "Removed"
+    };
+    for entry in &removed {
+        println!("{} {} ({} bytes)", action, entry.date, entry.size);
+    }
+    println!(
+        "{} {} entries, {} bytes in total",
+        action,
+        removed.len(),
+        removed.iter().map(|entry| entry.size).sum::<u64>()
+    );
     Ok(())
 }
+
+fn reparse(config: Config) -> anyhow::Result<()> {
+    let cache_path = config
+        .cache_path
+        .as_ref()
+        .with_context(|| "No cache is configured")?;
+    let cache = open_cache(&config)?;
+    let entries = cache
+        .entries()
+        .with_context(|| format!("Can't read cache {}", cache_path.display()))?;
+
+    let mut failed = 0;
+    for entry in &entries {
+        let metadata = match cache.metadata(entry.date) {
+            Ok(Some(metadata)) => format!(
+                "fetched from {} at {}",
+                metadata.source_url,
+                metadata.fetched_at.format("%Y-%m-%d %H:%M:%S UTC")
+            ),
+            Ok(None) => "no metadata".to_string(),
+            Err(e) => format!("broken metadata: {}", e),
+        };
+        match cache.load(entry.date) {
+            Ok(Some(manifest)) => println!(
+                "{}: {} packages, {}",
+                entry.date,
+                manifest.packages.len(),
+                metadata
+            ),
+            Ok(None) => println!("{}: no manifest, {}", entry.date, metadata),
+            Err(e) => {
+                failed += 1;
+                println!("{}: {}", entry.date, e);
+            }
+        }
+    }
+    println!(
+        "Parsed {} of {} entries",
+        entries.len() - failed,
+        entries.len()
+    );
+    if failed != 0 {
+        anyhow::bail!("{} entries can't be parsed", failed);
+    }
+    Ok(())
+}
+
+fn verify(config: Config, opts: Verify) -> anyhow::Result<()> {
+    let cache_path = config
+        .cache_path
+        .as_ref()
+        .with_context(|| "No cache is configured")?;
+    let cache = open_cache(&config)?;
+    let problems = cache
+        .verify()
+        .with_context(|| format!("Can't read cache {}", cache_path.display()))?;
+    let downloader = make_downloader(&config).set_cache(open_cache(&config)?);
+
+    let mut unresolved = 0;
+    for (entry, problem) in &problems {
+        println!("{}: {}", entry.date, problem);
+        if !opts.delete && !opts.redownload {
+            unresolved += 1;
+            continue;
+        }
+        cache
+            .remove(entry)
+            .with_context(|| format!("Can't remove the entry for {}", entry.date))?;
+        if opts.delete {
+            println!("  removed");
+            continue;
+        }
+        match downloader.get_manifest(entry.date) {
+            Ok(manifest) if manifest.date == entry.date => println!("  downloaded again"),
+            Ok(manifest) => {
+                unresolved += 1;
+                println!(
+                    "  downloaded again, but the manifest is for {}",
+                    manifest.date
+                );
+            }
+            Err(e) => {
+                unresolved += 1;
+                println!("  removed, but can't be downloaded again: {}", e);
+            }
+        }
+    }
+    println!("Found {} broken entries", problems.len());
+    if unresolved != 0 {
+        anyhow::bail!("{} entries are still broken or missing", unresolved);
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    match CmdOpts::from_args() {
+        CmdOpts::Render(cmd_opts) => render(load_config(&cmd_opts.config)?, cmd_opts.offline),
+        CmdOpts::PrintConfig(cmd_opts) => print_config(cmd_opts),
+        CmdOpts::Export(cmd_opts) => export(load_config(&cmd_opts.data.config)?, cmd_opts),
+        CmdOpts::Fetch(cmd_opts) => fetch(load_config(&cmd_opts.config)?, cmd_opts),
+        CmdOpts::Cache(CacheCmd::Prune(cmd_opts)) => {
+            prune(load_config(&cmd_opts.config)?, cmd_opts)
+        }
+        CmdOpts::Cache(CacheCmd::Reparse(cmd_opts)) => reparse(load_config(&cmd_opts)?),
+        CmdOpts::Cache(CacheCmd::Verify(cmd_opts)) => {
+            verify(load_config(&cmd_opts.config)?, cmd_opts)
+        }
+    }
+}
@@ -1,13 +1,22 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     fs::File,
     path::{Path, PathBuf},
 };
 
 use log::LevelFilter;
+use reqwest::StatusCode;
+use rustup_available_packages::{
+    cache::{Compression, RetentionPolicy},
+    filter::Filter,
+    table::SortOrder,
+    MissingPolicy, SkipBudget,
+};
 use serde::{Deserialize, Serialize, Serializer};
 use strum::EnumIter;
 
+use crate::tiers_table::inverse_tiers_map;
+
 /// Support tiers: https://doc.rust-lang.org/nightly/rustc/platform-support.html.
 #[derive(
     Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, EnumIter,
@@ -26,6 +35,7 @@ pub enum Tier {
     #[serde(rename = "Tier 3")]
     Tier3,
     #[doc(hidden)]
+    #[allow(clippy::enum_variant_names)]
     UnknownTier,
 }
 
@@ -56,6 +66,13 @@ pub struct Config {
     /// If omitted, the default channel is nightly.
     #[serde(default = "default_channel")]
     pub channel: String,
+    /// Base URLs to download manifests from, e.g. a mirror followed by
+    /// https://static.rust-lang.org/dist. The URLs are tried in order while
+    /// the previous ones respond with 404 or 5xx.
+    ///
+    /// If omitted, only https://static.rust-lang.org/dist is used.
+    #[serde(default)]
+    pub base_urls: Vec<String>,
     /// Verbosity level, might be one of the following: OFF, ERROR, WARN, INFO,
     /// DEBUG or TRACE.
     ///
@@ -68,6 +85,23 @@ pub struct Config {
     /// re-downloaded every time you run the tool.
     #[serde(default)]
     pub cache_path: Option<PathBuf>,
+    /// Limits of the cache, applied after the manifests are downloaded:
+    ///
+    /// keep_days: keep manifests only for this many most recent days;
+    /// max_size: maximum total size of the cache in bytes;
+    /// max_age_days: remove manifests older than this many days.
+    ///
+    /// All the limits are optional. If omitted, the cache grows forever. The
+    /// limits can also be applied manually with the "cache prune" command.
+    #[serde(default)]
+    pub cache_retention: RetentionPolicy,
+    /// Compression of newly cached manifests: none, gzip or zstd. Manifests
+    /// are read back regardless of their compression, so changing it doesn't
+    /// invalidate the cache.
+    ///
+    /// If omitted, manifests are stored uncompressed.
+    #[serde(default)]
+    pub cache_compression: Compression,
     ///A path where a file tree of available packages will be created. The tool
     /// will generate a set of files under a given *output* directory with the
     /// following pattern: file_tree_output/$target/$package, where $target
@@ -77,6 +111,171 @@ pub struct Config {
     /// which represents the latest date when the package was (is) available for
     /// that specific target.
     pub file_tree_output: PathBuf,
+    /// Status badges configuration.
+    #[serde(default)]
+    pub badges: Badges,
+    /// Packages to be listed in the rendered tables.
+    #[serde(default)]
+    pub packages: Packages,
+    /// Targets to render HTML pages and file trees for.
+    #[serde(default)]
+    pub targets: Targets,
+    /// Handling of days without manifests, e.g. when a nightly has failed to
+    /// build.
+    #[serde(default)]
+    pub missing_manifests: MissingManifests,
+}
+
+/// A policy of skipping days without manifests.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MissingManifests {
+    /// Maximum number of days to be skipped. If there are more missing days,
+    /// the tool fails.
+    ///
+    /// If omitted, the default value is 7.
+    #[serde(default = "default_skip_days")]
+    pub skip_days: usize,
+    /// Whether skip_days limits every gap of consecutive missing days rather
+    /// than the total number of missing days.
+    #[serde(default)]
+    pub per_gap: bool,
+    /// HTTP statuses which mean that a manifest is missing, e.g. [404, 403].
+    ///
+    /// If omitted, only 404 is treated as missing.
+    #[serde(default = "default_missing_statuses")]
+    pub statuses: Vec<u16>,
+    /// Whether manifests that can't be parsed are treated as missing.
+    #[serde(default)]
+    pub parse_errors: bool,
+}
+
+fn default_skip_days() -> usize {
+    7
+}
+
+fn default_missing_statuses() -> Vec<u16> {
+    vec![404]
+}
+
+impl Default for MissingManifests {
+    fn default() -> Self {
+        MissingManifests {
+            skip_days: default_skip_days(),
+            per_gap: false,
+            statuses: default_missing_statuses(),
+            parse_errors: false,
+        }
+    }
+}
+
+impl MissingManifests {
+    /// Makes a missing manifests policy for the downloader.
+    pub fn policy(&self) -> anyhow::Result<MissingPolicy> {
+        let budget = if self.per_gap {
+            SkipBudget::PerGap(self.skip_days)
+        } else {
+            SkipBudget::Total(self.skip_days)
+        };
+        let statuses = self
+            .statuses
+            .iter()
+            .map(|&status| StatusCode::from_u16(status))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MissingPolicy::new(budget)
+            .missing_statuses(statuses)
+            .parse_errors_as_missing(self.parse_errors))
+    }
+}
+
+/// Selection of packages listed in the rendered HTML pages and exported
+/// tables. The file tree always contains all the packages.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Packages {
+    /// Lists of package names or glob patterns (e.g. "*-preview") to include
+    /// and exclude, like this:
+    ///
+    /// include: ["rust-*", "miri"]
+    /// exclude: ["rust-docs"]
+    ///
+    /// If "include" is omitted or empty, all the packages are included.
+    #[serde(flatten)]
+    pub filter: Filter,
+    /// Whether to hide packages that haven't been available on any of the
+    /// displayed dates.
+    ///
+    /// Disabled by default.
+    #[serde(default)]
+    pub hide_unseen: bool,
+    /// An order in which packages are listed, might be one of the following:
+    /// name, last-available (the longest broken first), current-availability
+    /// (currently broken first), availability-ratio (the least available over
+    /// the displayed dates first) or priority (as listed in "priority").
+    ///
+    /// If omitted, packages are sorted by name.
+    #[serde(default)]
+    pub sort: PackagesOrder,
+    /// A list of package names in the desired order, used when "sort" is
+    /// priority. Packages not listed here follow in an alphabetical order.
+    #[serde(default)]
+    pub priority: Vec<String>,
+}
+
+impl Packages {
+    /// Makes a table sort order according to the configuration.
+    pub fn sort_order(&self) -> SortOrder {
+        match self.sort {
+            PackagesOrder::Name => SortOrder::Name,
+            PackagesOrder::LastAvailable => SortOrder::LastAvailable,
+            PackagesOrder::CurrentAvailability => SortOrder::CurrentAvailability,
+            PackagesOrder::AvailabilityRatio => SortOrder::AvailabilityRatio,
+            PackagesOrder::Priority => SortOrder::Priority(self.priority.clone()),
+        }
+    }
+}
+
+/// An order in which packages are listed.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum PackagesOrder {
+    /// See [`SortOrder::Name`].
+    #[default]
+    Name,
+    /// See [`SortOrder::LastAvailable`].
+    LastAvailable,
+    /// See [`SortOrder::CurrentAvailability`].
+    CurrentAvailability,
+    /// See [`SortOrder::AvailabilityRatio`].
+    AvailabilityRatio,
+    /// See [`SortOrder::Priority`].
+    Priority,
+}
+
+/// Status badges configuration.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Badges {
+    /// Whether to generate status badges. If enabled, an SVG badge with the
+    /// current status and the last available date will be generated at
+    /// file_tree_output/$target/$package.svg for every package.
+    ///
+    /// Disabled by default.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Visual style of the badges, might be either "flat" or "flat-square".
+    ///
+    /// If omitted, the default style is "flat".
+    #[serde(default)]
+    pub style: BadgeStyle,
+}
+
+/// Visual style of a status badge.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum BadgeStyle {
+    /// Rounded corners and a slight gradient.
+    #[default]
+    Flat,
+    /// Square corners and no gradient.
+    FlatSquare,
 }
 
 /// Html-related configuration
@@ -91,6 +290,111 @@ pub struct Html {
     #[serde(default)]
     #[serde(serialize_with = "ordered_map")]
     pub tiers: HashMap<Tier, Vec<String>>,
+    /// Details of the targets listed in the tiers lists.
+    #[serde(default)]
+    #[serde(serialize_with = "ordered_map")]
+    pub target_info: HashMap<String, TargetInfo>,
+    /// How targets are grouped in the navigation, might be one of the
+    /// following: tiers, os (by operating system families, like Linux or
+    /// Apple) or arch (by architecture families, like x86 or ARM).
+    ///
+    /// If omitted, targets are grouped by tiers.
+    #[serde(default)]
+    pub navigation: Navigation,
+}
+
+/// Grouping of targets in the navigation.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Navigation {
+    /// By support tiers.
+    #[default]
+    Tiers,
+    /// By operating system families.
+    Os,
+    /// By architecture families.
+    Arch,
+}
+
+/// Details of a target from the platform support page.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TargetInfo {
+    /// Standard library support, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub std: Option<StdSupport>,
+    /// Whether the target ships host tools, like rustc and cargo.
+    #[serde(default)]
+    pub host_tools: bool,
+    /// Notes on the target, usually a short description.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+}
+
+/// Standard library support level.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum StdSupport {
+    /// Full standard library.
+    Full,
+    /// Only the core library (no_std).
+    NoStd,
+    /// Unknown or work in progress.
+    Unknown,
+}
+
+impl StdSupport {
+    /// Parses a mark used in the "std" column of the platform support page:
+    /// "✓" for full support, "*" for no_std and "?" for unknown.
+    pub fn from_mark(mark: &str) -> Option<Self> {
+        match mark.trim() {
+            "" => None,
+            "✓" => Some(StdSupport::Full),
+            "*" => Some(StdSupport::NoStd),
+            _ => Some(StdSupport::Unknown),
+        }
+    }
+}
+
+/// Selection of targets for which HTML pages and file trees are generated.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Targets {
+    /// Lists of target names or glob patterns (e.g. "*-windows-*") to include
+    /// and exclude, like this:
+    ///
+    /// include: ["x86_64-*", "aarch64-apple-darwin"]
+    /// exclude: ["*-musl"]
+    ///
+    /// If "include" is omitted or empty, all the targets are included.
+    #[serde(flatten)]
+    pub filter: Filter,
+    /// Only targets of the listed tiers (e.g. ["Tier 1", "Tier 2"]) are
+    /// rendered, according to the "tiers" lists. The target must also pass
+    /// the "include" and "exclude" lists.
+    ///
+    /// If omitted or empty, targets of all the tiers are rendered.
+    #[serde(default)]
+    pub tiers: Vec<Tier>,
+}
+
+impl Targets {
+    /// Selects the targets that should be rendered.
+    pub fn select<'a>(
+        &self,
+        targets: HashSet<&'a str>,
+        tiers: &HashMap<Tier, Vec<String>>,
+    ) -> HashSet<&'a str> {
+        let tiers_map = inverse_tiers_map(tiers);
+        targets
+            .into_iter()
+            .filter(|target| self.filter.matches(target))
+            .filter(|target| {
+                self.tiers.is_empty() || {
+                    let tier = tiers_map.get(target).copied().unwrap_or(Tier::UnknownTier);
+                    self.tiers.contains(&tier)
+                }
+            })
+            .collect()
+    }
 }
 
 fn ordered_map<S: Serializer, K: Ord + Serialize, V: Serialize>(
@@ -110,3 +414,78 @@ impl Config {
         Ok(serde_yaml::from_reader(f)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(config: &str) -> Vec<&'static str> {
+        let targets: Targets = serde_yaml::from_str(config).unwrap();
+        let all = [
+            "x86_64-unknown-linux-gnu",
+            "x86_64-unknown-linux-musl",
+            "x86_64-pc-windows-msvc",
+            "aarch64-apple-darwin",
+            "wasm32-unknown-unknown",
+        ];
+        let tiers = vec![
+            (
+                Tier::Tier1,
+                vec![
+                    "x86_64-unknown-linux-gnu".to_string(),
+                    "x86_64-pc-windows-msvc".to_string(),
+                ],
+            ),
+            (
+                Tier::Tier2,
+                vec![
+                    "x86_64-unknown-linux-musl".to_string(),
+                    "aarch64-apple-darwin".to_string(),
+                ],
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let mut selected: Vec<_> = targets
+            .select(all.iter().copied().collect(), &tiers)
+            .into_iter()
+            .collect();
+        selected.sort_unstable();
+        selected
+    }
+
+    #[test]
+    fn select_targets() {
+        assert_eq!(5, select("{}").len());
+        assert_eq!(
+            vec!["aarch64-apple-darwin", "x86_64-unknown-linux-gnu"],
+            select(r#"include: ["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"]"#)
+        );
+        assert_eq!(
+            vec![
+                "aarch64-apple-darwin",
+                "x86_64-pc-windows-msvc",
+                "x86_64-unknown-linux-gnu",
+            ],
+            select(r#"exclude: ["*-musl", "wasm32-*"]"#)
+        );
+        assert_eq!(
+            vec!["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"],
+            select(r#"include: ["x86_64-*-linux-*"]"#)
+        );
+        assert_eq!(
+            vec!["x86_64-pc-windows-msvc", "x86_64-unknown-linux-gnu"],
+            select(r#"tiers: ["Tier 1"]"#)
+        );
+        assert_eq!(
+            vec!["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"],
+            select(
+                r#"
+                include: ["x86_64-*"]
+                exclude: ["*-windows-*"]
+                tiers: ["Tier 1", "Tier 2"]
+                "#
+            )
+        );
+    }
+}
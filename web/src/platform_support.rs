@@ -0,0 +1,492 @@
+//! Parsers of the platform support page:
+//! https://doc.rust-lang.org/nightly/rustc/platform-support.html.
+//!
+//! The page is split into sections by tiers, like "Tier 2 with Host Tools" or
+//! "Tier 3", each of them containing a table of targets.
+
+use anyhow::Context;
+use tl::{HTMLTag, Node, Parser, ParserOptions};
+
+use crate::opts::{StdSupport, TargetInfo, Tier};
+
+/// A target listed on the platform support page.
+#[derive(Debug)]
+pub struct PlatformTarget {
+    /// Target triple.
+    pub name: String,
+    /// Support tier.
+    pub tier: Tier,
+    /// Target details.
+    pub info: TargetInfo,
+}
+
+/// A section of the page that lists targets of a tier.
+struct Section {
+    tier: Tier,
+    /// Whether the targets of the section ship host tools, if the section
+    /// heading says so.
+    host_tools: Option<bool>,
+}
+
+impl Section {
+    /// Parses a heading like "Tier 2 without Host Tools".
+    fn from_heading(heading: &str) -> Option<Self> {
+        let heading = heading.trim().to_lowercase();
+        let rest = heading.strip_prefix("tier ")?;
+        let tier = match rest.split_whitespace().next()? {
+            "1" => Tier::Tier1,
+            "2" => Tier::Tier2,
+            "2.5" => Tier::Tier25,
+            "3" => Tier::Tier3,
+            _ => return None,
+        };
+        let host_tools = if rest.contains("without host tools") {
+            Some(false)
+        } else if rest.contains("with host tools") {
+            Some(true)
+        } else {
+            None
+        };
+        Some(Section { tier, host_tools })
+    }
+}
+
+/// Positions of known columns in a tier table.
+struct Columns {
+    target: usize,
+    std: Option<usize>,
+    host: Option<usize>,
+    notes: Option<usize>,
+}
+
+impl Columns {
+    fn new(header: &[String]) -> anyhow::Result<Self> {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|column| column.trim().eq_ignore_ascii_case(name))
+        };
+        Ok(Columns {
+            target: find("target")
+                .with_context(|| format!("Table has no target column: {:?}", header))?,
+            std: find("std"),
+            host: find("host"),
+            notes: find("notes"),
+        })
+    }
+}
+
+/// A table cell.
+struct Cell {
+    /// Human-readable text of the cell.
+    text: String,
+    /// Contents of the first code element of the cell, if any.
+    code: Option<String>,
+}
+
+fn make_target(
+    section: &Section,
+    columns: &Columns,
+    cells: &[Cell],
+) -> anyhow::Result<PlatformTarget> {
+    let texts = || cells.iter().map(|cell| &cell.text).collect::<Vec<_>>();
+    let name = cells
+        .get(columns.target)
+        .and_then(|cell| cell.code.clone())
+        .with_context(|| {
+            format!(
+                "Table row does not have a code element in its target column: {:?}",
+                texts()
+            )
+        })?;
+    let text = |column: Option<usize>| {
+        column
+            .and_then(|column| cells.get(column))
+            .map(|cell| cell.text.as_str())
+    };
+    let std = match text(columns.std) {
+        Some(std) => StdSupport::from_mark(std),
+        // Host tools can't be built without the standard library, hence
+        // sections of targets with host tools don't list it explicitly.
+        None if section.host_tools == Some(true) => Some(StdSupport::Full),
+        None => None,
+    };
+    let host_tools = section
+        .host_tools
+        .unwrap_or_else(|| text(columns.host).is_some_and(|host| host.contains('✓')));
+    Ok(PlatformTarget {
+        name,
+        tier: section.tier,
+        info: TargetInfo {
+            std,
+            host_tools,
+            notes: text(columns.notes).unwrap_or_default().to_string(),
+        },
+    })
+}
+
+/// Parses the HTML version of the page.
+pub fn parse_html(contents: &str) -> anyhow::Result<Vec<PlatformTarget>> {
+    let dom = tl::parse(contents, ParserOptions::default())?;
+    let parser = dom.parser();
+
+    let mut section = None;
+    let mut targets = Vec::new();
+    // Nodes are stored in the document order, so every table follows the
+    // heading of its section.
+    for tag in dom.nodes().iter().filter_map(Node::as_tag) {
+        let name = tag.name().as_utf8_str();
+        if name == "h1" || name == "h2" {
+            section = Section::from_heading(&cell_text(tag, parser));
+        } else if name == "table" {
+            let section = match &section {
+                Some(section) => section,
+                None => continue,
+            };
+            let header: Vec<String> = select(tag, parser, "th")
+                .map(|th| cell_text(th, parser))
+                .collect();
+            let columns = Columns::new(&header)?;
+            for row in select(tag, parser, "tr") {
+                let cells: Vec<Cell> = select(row, parser, "td")
+                    .map(|td| Cell {
+                        text: cell_text(td, parser),
+                        code: select(td, parser, "code")
+                            .next()
+                            .map(|code| cell_text(code, parser)),
+                    })
+                    .collect();
+                if cells.is_empty() {
+                    // A header row.
+                    continue;
+                }
+                targets.push(make_target(section, &columns, &cells)?);
+            }
+        }
+    }
+    anyhow::ensure!(!targets.is_empty(), "No tier tables found");
+    Ok(targets)
+}
+
+/// Finds all the descendants of a tag that match a selector.
+fn select<'a, 'b: 'a>(
+    tag: &'a HTMLTag<'b>,
+    parser: &'a Parser<'b>,
+    selector: &'a str,
+) -> impl Iterator<Item = &'a HTMLTag<'b>> + 'a {
+    tag.query_selector(parser, selector)
+        .into_iter()
+        .flatten()
+        .filter_map(move |handle| handle.get(parser))
+        .filter_map(Node::as_tag)
+}
+
+/// Extracts a text from a tag, ignoring footnote references.
+fn cell_text(tag: &HTMLTag, parser: &Parser) -> String {
+    fn collect(tag: &HTMLTag, parser: &Parser, out: &mut String) {
+        for child in tag.children().top().iter().filter_map(|h| h.get(parser)) {
+            match child {
+                Node::Raw(raw) => out.push_str(&raw.as_utf8_str()),
+                Node::Tag(tag) if tag.name() == "sup" => {}
+                Node::Tag(tag) => collect(tag, parser, out),
+                Node::Comment(_) => {}
+            }
+        }
+    }
+
+    let mut text = String::new();
+    collect(tag, parser, &mut text);
+    normalize(&decode_entities(&text))
+}
+
+/// Decodes the most common HTML entities.
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Collapses all the whitespaces into single spaces.
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parses the markdown source of the page (`src/doc/rustc/src/platform-support.md`
+/// in the rust-lang/rust repository).
+pub fn parse_markdown(contents: &str) -> anyhow::Result<Vec<PlatformTarget>> {
+    let is_delimiter_row = |line: &str| {
+        line.contains('-')
+            && line
+                .trim()
+                .chars()
+                .all(|c| matches!(c, '|' | '-' | ':' | ' '))
+    };
+
+    let mut section = None;
+    let mut targets = Vec::new();
+    let mut lines = contents.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with('#') {
+            let level = line.chars().take_while(|&c| c == '#').count();
+            if level <= 2 {
+                section = Section::from_heading(line.trim_start_matches('#'));
+            }
+            continue;
+        }
+        // A table starts with a header row followed by a delimiter row.
+        if !line.contains('|') || !lines.peek().is_some_and(|next| is_delimiter_row(next)) {
+            continue;
+        }
+        lines.next();
+        let section = match &section {
+            Some(section) => section,
+            None => continue,
+        };
+        let header: Vec<String> = split_row(line).map(markdown_text).collect();
+        let columns = Columns::new(&header)?;
+        while let Some(row) = lines.next_if(|row| row.contains('|')) {
+            let cells: Vec<Cell> = split_row(row)
+                .map(|cell| Cell {
+                    text: markdown_text(cell),
+                    code: cell.split('`').nth(1).map(str::to_string),
+                })
+                .collect();
+            targets.push(make_target(section, &columns, &cells)?);
+        }
+    }
+    anyhow::ensure!(!targets.is_empty(), "No tier tables found");
+    Ok(targets)
+}
+
+/// Splits a markdown table row into cells.
+fn split_row(row: &str) -> impl Iterator<Item = &str> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    row.split('|')
+}
+
+/// Strips markdown markup, i.e. code spans, links and footnote references,
+/// from a text.
+fn markdown_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = match after.find(']') {
+            Some(end) => end,
+            None => {
+                out.push('[');
+                rest = after;
+                continue;
+            }
+        };
+        let inner = &after[..end];
+        if !inner.starts_with('^') {
+            out.push_str(inner);
+        }
+        rest = &after[end + 1..];
+        // Skip a link destination or a reference label.
+        for (open, close) in [('(', ')'), ('[', ']')] {
+            if let Some(destination) = rest.strip_prefix(open) {
+                if let Some(end) = destination.find(close) {
+                    rest = &destination[end + 1..];
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+    normalize(&out.replace('`', ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Summarizes a target as `(name, tier, std, host_tools, notes)`.
+    fn summary(target: &PlatformTarget) -> (&str, Tier, Option<StdSupport>, bool, &str) {
+        (
+            &target.name,
+            target.tier,
+            target.info.std,
+            target.info.host_tools,
+            &target.info.notes,
+        )
+    }
+
+    const HTML: &str = r##"<html><body>
+<h1 id="platform-support"><a class="header" href="#platform-support">Platform Support</a></h1>
+<table><thead><tr><th>not a tier</th></tr></thead></table>
+<h2 id="tier-1-with-host-tools"><a class="header" href="#tier-1">Tier 1 with Host Tools</a></h2>
+<table>
+<thead><tr><th>target</th><th>notes</th></tr></thead>
+<tbody>
+<tr><td><a href="platform-support/linux.html"><code>x86_64-unknown-linux-gnu</code></a></td>
+<td>64-bit Linux (kernel 3.2+, glibc 2.17+) <sup class="footnote-reference"><a href="#x">1</a></sup></td></tr>
+</tbody></table>
+<h2 id="tier-2-without-host-tools">Tier 2 without Host Tools</h2>
+<table>
+<thead><tr><th>target</th><th>std</th><th>notes</th></tr></thead>
+<tbody>
+<tr><td><code>thumbv7em-none-eabi</code></td><td>*</td><td>Bare ARMv7E-M &amp; friends</td></tr>
+<tr><td><code>wasm32-unknown-unknown</code></td><td>✓</td><td>WebAssembly</td></tr>
+</tbody></table>
+<h2 id="tier-3">Tier 3</h2>
+<table>
+<thead><tr><th>target</th><th>std</th><th>host</th><th>notes</th></tr></thead>
+<tbody>
+<tr><td><code>aarch64-unknown-hermit</code></td><td>✓</td><td></td><td>ARM64 Hermit</td></tr>
+<tr><td><code>x86_64-unknown-haiku</code></td><td>?</td><td>✓</td><td>64-bit Haiku</td></tr>
+</tbody></table>
+</body></html>"##;
+
+    const MARKDOWN: &str = "# Platform Support
+
+target | notes
+-------|------
+`not-a-tier` | ignored
+
+## Tier 1 with Host Tools
+
+target | notes
+-------|-------
+[`x86_64-unknown-linux-gnu`](platform-support/linux.md) | 64-bit Linux (kernel 3.2+) [^x86_32-floats]
+
+### Tier 1 target policy
+
+## Tier 2 without Host Tools
+
+target | std | notes
+-------|:---:|-------
+`thumbv7em-none-eabi` | * | Bare [ARMv7E-M][arm] and `friends`
+`wasm32-unknown-unknown` | ✓ | WebAssembly
+
+## Tier 3
+
+| target | std | host | notes |
+|--------|-----|------|-------|
+| `aarch64-unknown-hermit` | ✓ |  | ARM64 Hermit |
+| `x86_64-unknown-haiku` | ? | ✓ | 64-bit Haiku |
+
+[arm]: https://developer.arm.com
+";
+
+    fn check(targets: &[PlatformTarget]) {
+        let targets: Vec<_> = targets.iter().map(summary).collect();
+        assert_eq!(
+            vec![
+                (
+                    "x86_64-unknown-linux-gnu",
+                    Tier::Tier1,
+                    Some(StdSupport::Full),
+                    true,
+                    "64-bit Linux (kernel 3.2+, glibc 2.17+)"
+                ),
+                (
+                    "thumbv7em-none-eabi",
+                    Tier::Tier2,
+                    Some(StdSupport::NoStd),
+                    false,
+                    "Bare ARMv7E-M & friends"
+                ),
+                (
+                    "wasm32-unknown-unknown",
+                    Tier::Tier2,
+                    Some(StdSupport::Full),
+                    false,
+                    "WebAssembly"
+                ),
+                (
+                    "aarch64-unknown-hermit",
+                    Tier::Tier3,
+                    Some(StdSupport::Full),
+                    false,
+                    "ARM64 Hermit"
+                ),
+                (
+                    "x86_64-unknown-haiku",
+                    Tier::Tier3,
+                    Some(StdSupport::Unknown),
+                    true,
+                    "64-bit Haiku"
+                ),
+            ],
+            targets
+        );
+    }
+
+    #[test]
+    fn html() {
+        check(&parse_html(HTML).unwrap());
+    }
+
+    #[test]
+    fn markdown() {
+        let mut targets = parse_markdown(MARKDOWN).unwrap();
+        // Markdown notes keep the text of links, but the HTML fixture has
+        // none, so align them before comparing.
+        assert_eq!("64-bit Linux (kernel 3.2+)", targets[0].info.notes);
+        targets[0].info.notes = "64-bit Linux (kernel 3.2+, glibc 2.17+)".into();
+        assert_eq!("Bare ARMv7E-M and friends", targets[1].info.notes);
+        targets[1].info.notes = "Bare ARMv7E-M & friends".into();
+        check(&targets);
+    }
+
+    #[test]
+    fn headings() {
+        let section = Section::from_heading(" Tier 2 with Host Tools ").unwrap();
+        assert_eq!(
+            (Tier::Tier2, Some(true)),
+            (section.tier, section.host_tools)
+        );
+        let section = Section::from_heading("Tier 2.5").unwrap();
+        assert_eq!((Tier::Tier25, None), (section.tier, section.host_tools));
+        assert!(Section::from_heading("Tier 4").is_none());
+        assert!(Section::from_heading("Platform Support").is_none());
+    }
+
+    #[test]
+    fn markdown_markup() {
+        assert_eq!(
+            "see the docs",
+            markdown_text("see [the docs](https://example.com)")
+        );
+        assert_eq!("ARM64 iOS", markdown_text("ARM64 iOS [^apple]"));
+        assert_eq!("a [b", markdown_text("a [b"));
+        assert_eq!(
+            "x86_64-apple-darwin",
+            markdown_text("`x86_64-apple-darwin`")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let no_target_column = "## Tier 1\n\nname | notes\n-----|------\n`a` | b\n";
+        let error = parse_markdown(no_target_column).unwrap_err();
+        assert!(error.to_string().contains("no target column"), "{}", error);
+
+        let no_code = "## Tier 1\n\ntarget | notes\n-------|------\nplain | b\n";
+        let error = parse_markdown(no_code).unwrap_err();
+        assert!(error.to_string().contains("code element"), "{}", error);
+
+        let no_tiers = "# Platform Support\n\ntarget | notes\n-------|------\n`a` | b\n";
+        let error = parse_markdown(no_tiers).unwrap_err();
+        assert!(error.to_string().contains("No tier tables"), "{}", error);
+
+        let no_target_column = "<h2>Tier 1</h2><table><tr><th>name</th></tr>\
+            <tr><td><code>a</code></td></tr></table>";
+        let error = parse_html(no_target_column).unwrap_err();
+        assert!(error.to_string().contains("no target column"), "{}", error);
+
+        let no_code = "<h2>Tier 1</h2><table><tr><th>target</th></tr>\
+            <tr><td>a</td></tr></table>";
+        let error = parse_html(no_code).unwrap_err();
+        assert!(error.to_string().contains("code element"), "{}", error);
+
+        let error = parse_html("<h1>Platform Support</h1><p>Nothing</p>").unwrap_err();
+        assert!(error.to_string().contains("No tier tables"), "{}", error);
+    }
+}
@@ -0,0 +1,87 @@
+//! A progress bar for long downloads.
+
+use chrono::NaiveDate;
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use indicatif_log_bridge::LogWrapper;
+use rustup_available_packages::{Error, ProgressObserver};
+use std::{
+    io::{self, IsTerminal},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+/// All the progress bars are drawn through this, so log messages don't garble them.
+fn multi_progress() -> &'static MultiProgress {
+    static MULTI_PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+    MULTI_PROGRESS.get_or_init(MultiProgress::new)
+}
+
+/// Initializes the logger so that it plays nicely with progress bars.
+pub fn init_logger(builder: &mut env_logger::Builder) {
+    LogWrapper::new(multi_progress().clone(), builder.build())
+        .try_init()
+        .expect("The logger is initialized twice");
+}
+
+/// Shows a progress bar of retrieved manifests. Nothing is shown unless stderr is a terminal.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    bar: ProgressBar,
+    downloaded: Arc<AtomicU64>,
+}
+
+impl ProgressReporter {
+    /// Creates a progress bar for a given number of days.
+    pub fn new(days: usize) -> Self {
+        let bar = if io::stderr().is_terminal() {
+            multi_progress().add(ProgressBar::new(days as u64))
+        } else {
+            ProgressBar::hidden()
+        };
+        bar.set_style(
+            ProgressStyle::with_template("{elapsed:>4} [{bar:40}] {pos}/{len} days, {msg}")
+                .expect("The template is valid")
+                .progress_chars("=> "),
+        );
+        ProgressReporter {
+            bar,
+            downloaded: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Advances the bar for a day that hasn't been passed to the downloader at all.
+    pub fn skip_day(&self) {
+        self.bar.inc(1);
+    }
+
+    /// Removes the bar from the terminal.
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+impl ProgressObserver for ProgressReporter {
+    fn started(&self, date: Option<NaiveDate>) {
+        match date {
+            Some(date) => self.bar.set_message(format!("fetching {}", date)),
+            None => self.bar.set_message("fetching the latest manifest"),
+        }
+    }
+
+    fn finished(&self, _: NaiveDate, bytes: u64) {
+        let total = self.downloaded.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.bar
+            .set_message(format!("{} downloaded", HumanBytes(total)));
+        self.bar.inc(1);
+    }
+
+    fn cached(&self, _: NaiveDate) {
+        self.bar.inc(1);
+    }
+
+    fn failed(&self, _: Option<NaiveDate>, _: &Error) {
+        self.bar.inc(1);
+    }
+}
@@ -1,17 +1,52 @@
-use crate::opts::Tier;
+use crate::opts::{Navigation, StdSupport, TargetInfo, Tier};
+use rustup_available_packages::TargetComponents;
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
 };
 
 #[derive(serde::Serialize)]
 pub struct TiersTable<'a> {
-    /// A list of tier names and their targets.
-    tiers_and_targets: Vec<(Tier, Vec<(String, bool)>)>,
+    /// A list of groups (tiers or target families) and their targets.
+    tiers_and_targets: Vec<(GroupName, Vec<TierTarget>)>,
     unknown_tier: Vec<Cow<'a, str>>,
+    #[serde(skip)]
+    target_tiers: HashMap<String, Tier>,
 }
 
-fn inverse_tiers_map(map: &HashMap<Tier, Vec<String>>) -> HashMap<&str, Tier> {
+/// A name of a group of targets in the navigation.
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+enum GroupName {
+    Tier(Tier),
+    Family(&'static str),
+}
+
+/// A target listed in a tier.
+#[derive(serde::Serialize)]
+pub struct TierTarget {
+    /// Target name.
+    name: String,
+    /// Whether a page is rendered for the target.
+    rendered: bool,
+    /// Whether the target ships host tools.
+    host_tools: bool,
+    /// Standard library support, if known.
+    std: Option<StdSupport>,
+    /// Notes on the target, usually a short description.
+    notes: String,
+}
+
+/// A target listed in a tier along with the tier.
+#[derive(serde::Serialize)]
+pub struct CurrentTarget<'a> {
+    tier: Tier,
+    #[serde(flatten)]
+    target: &'a TierTarget,
+}
+
+/// Maps targets onto their tiers.
+pub fn inverse_tiers_map(map: &HashMap<Tier, Vec<String>>) -> HashMap<&str, Tier> {
     map.iter()
         .flat_map(|(tier, targets)| targets.iter().map(move |target| (target as &str, *tier)))
         .collect()
@@ -21,7 +56,7 @@ fn find_unknown<'a>(
     tiers: &HashMap<Tier, Vec<String>>,
     targets: &HashSet<&'a str>,
 ) -> Vec<Cow<'a, str>> {
-    let inversed_tiers = inverse_tiers_map(&tiers);
+    let inversed_tiers = inverse_tiers_map(tiers);
     let not_listed = targets.iter().filter_map(|&target| {
         let tier = inversed_tiers
             .get(target)
@@ -44,28 +79,265 @@ fn find_unknown<'a>(
     result
 }
 
+/// Names an operating system family of a target.
+fn os_family(components: &TargetComponents) -> &'static str {
+    if components.arch.starts_with("wasm") {
+        return "WebAssembly";
+    }
+    match (components.os, components.env) {
+        (Some("linux"), Some(env)) if env.starts_with("android") => "Android",
+        (Some("linux"), _) => "Linux",
+        (Some("windows"), _) => "Windows",
+        (Some("darwin" | "macos" | "ios" | "tvos" | "watchos" | "visionos"), _) => "Apple",
+        (Some("freebsd" | "netbsd" | "openbsd" | "dragonfly"), _) => "BSD",
+        (Some("solaris" | "illumos"), _) => "Solaris",
+        (Some("none" | "unknown" | "uefi") | None, _) => "Bare metal",
+        _ => "Other",
+    }
+}
+
+/// Names an architecture family of a target.
+fn arch_family(components: &TargetComponents) -> &'static str {
+    let arch = components.arch;
+    let starts_with = |prefixes: &[&str]| prefixes.iter().any(|prefix| arch.starts_with(prefix));
+    if starts_with(&["x86_64", "i386", "i586", "i686"]) {
+        "x86"
+    } else if starts_with(&["aarch64", "arm64"]) {
+        "AArch64"
+    } else if starts_with(&["arm", "thumb"]) {
+        "ARM"
+    } else if starts_with(&["riscv"]) {
+        "RISC-V"
+    } else if starts_with(&["powerpc"]) {
+        "PowerPC"
+    } else if starts_with(&["mips"]) {
+        "MIPS"
+    } else if starts_with(&["wasm"]) {
+        "WebAssembly"
+    } else if starts_with(&["loongarch"]) {
+        "LoongArch"
+    } else if starts_with(&["sparc"]) {
+        "SPARC"
+    } else {
+        "Other"
+    }
+}
+
+/// Groups targets by families, the "Other" family goes last.
+fn group_by_family(
+    targets: impl IntoIterator<Item = String>,
+    family: fn(&TargetComponents) -> &'static str,
+) -> Vec<(GroupName, Vec<String>)> {
+    let mut groups: BTreeMap<(bool, &'static str), Vec<String>> = BTreeMap::new();
+    for target in targets {
+        let family = family(&TargetComponents::parse(&target));
+        groups
+            .entry((family == "Other", family))
+            .or_default()
+            .push(target);
+    }
+    groups
+        .into_iter()
+        .map(|((_, family), targets)| (GroupName::Family(family), targets))
+        .collect()
+}
+
 impl<'a> TiersTable<'a> {
-    pub fn new(tiers: HashMap<Tier, Vec<String>>, targets: &HashSet<&'a str>) -> Self {
-        let unknown = find_unknown(&tiers, targets);
-        let mut v: Vec<_> = tiers
+    pub fn new(
+        tiers: HashMap<Tier, Vec<String>>,
+        target_info: &HashMap<String, TargetInfo>,
+        targets: &HashSet<&'a str>,
+        navigation: Navigation,
+    ) -> Self {
+        let target_tiers = inverse_tiers_map(&tiers)
+            .into_iter()
+            .map(|(target, tier)| (target.to_string(), tier))
+            .collect();
+        let (groups, unknown) = match navigation {
+            Navigation::Tiers => {
+                let unknown = find_unknown(&tiers, targets);
+                let mut groups: Vec<_> = tiers
+                    .into_iter()
+                    .filter(|(tier, _)| tier != &Tier::UnknownTier)
+                    .collect();
+                groups.sort_unstable_by_key(|(tier, _)| *tier);
+                let groups = groups
+                    .into_iter()
+                    .map(|(tier, tier_targets)| (GroupName::Tier(tier), tier_targets))
+                    .collect();
+                (groups, unknown)
+            }
+            Navigation::Os | Navigation::Arch => {
+                // Every target is listed under some family, so there are no
+                // unknown ones.
+                let all_targets: HashSet<String> = tiers
+                    .into_values()
+                    .flatten()
+                    .chain(targets.iter().map(ToString::to_string))
+                    .collect();
+                let family = match navigation {
+                    Navigation::Os => os_family,
+                    _ => arch_family,
+                };
+                (group_by_family(all_targets, family), Vec::new())
+            }
+        };
+        let tiers_and_targets = groups
             .into_iter()
-            .filter(|(tier, _)| tier != &Tier::UnknownTier)
-            .map(|(tier, mut tier_targets)| {
-                tier_targets.sort_unstable();
-                let tier_targets = tier_targets
+            .map(|(name, mut group_targets)| {
+                group_targets.sort_unstable();
+                let group_targets = group_targets
                     .into_iter()
                     .map(|target| {
-                        let contains = targets.contains(&target as &str);
-                        (target, contains)
+                        let info = target_info.get(&target).cloned().unwrap_or_default();
+                        TierTarget {
+                            rendered: targets.contains(&target as &str),
+                            host_tools: info.host_tools,
+                            std: info.std,
+                            notes: info.notes,
+                            name: target,
+                        }
                     })
                     .collect();
-                (tier, tier_targets)
+                (name, group_targets)
             })
             .collect();
-        v.sort_unstable();
         TiersTable {
-            tiers_and_targets: v,
+            tiers_and_targets,
             unknown_tier: unknown,
+            target_tiers,
+        }
+    }
+}
+
+impl TiersTable<'_> {
+    /// Finds a target in the tiers lists.
+    pub fn find(&self, target: &str) -> Option<CurrentTarget<'_>> {
+        let tier = *self.target_tiers.get(target)?;
+        self.tiers_and_targets
+            .iter()
+            .flat_map(|(_, group_targets)| group_targets)
+            .find(|group_target| group_target.name == target)
+            .map(|group_target| CurrentTarget {
+                tier,
+                target: group_target,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os(triple: &str) -> &'static str {
+        os_family(&TargetComponents::parse(triple))
+    }
+
+    fn arch(triple: &str) -> &'static str {
+        arch_family(&TargetComponents::parse(triple))
+    }
+
+    #[test]
+    fn families() {
+        assert_eq!("Apple", os("aarch64-apple-darwin"));
+        assert_eq!("Apple", os("aarch64-apple-ios-macabi"));
+        assert_eq!("Windows", os("x86_64-pc-windows-gnu"));
+        assert_eq!("Windows", os("i686-pc-windows-msvc"));
+        assert_eq!("Windows", os("aarch64-pc-windows-gnullvm"));
+        assert_eq!("Linux", os("x86_64-unknown-linux-gnu"));
+        assert_eq!("Linux", os("armv7-unknown-linux-musleabihf"));
+        assert_eq!("Android", os("aarch64-linux-android"));
+        assert_eq!("Bare metal", os("thumbv7em-none-eabihf"));
+        assert_eq!("Bare metal", os("riscv32imac-unknown-none-elf"));
+        assert_eq!("WebAssembly", os("wasm32-unknown-unknown"));
+        assert_eq!("BSD", os("x86_64-unknown-freebsd"));
+        assert_eq!("Other", os("x86_64-unknown-haiku"));
+
+        assert_eq!("x86", arch("i686-pc-windows-msvc"));
+        assert_eq!("AArch64", arch("aarch64-apple-darwin"));
+        assert_eq!("AArch64", arch("arm64ec-pc-windows-msvc"));
+        assert_eq!("ARM", arch("thumbv7em-none-eabihf"));
+        assert_eq!("RISC-V", arch("riscv64gc-unknown-linux-gnu"));
+        assert_eq!("Other", arch("s390x-unknown-linux-gnu"));
+    }
+
+    #[test]
+    fn groups() {
+        let targets = [
+            "x86_64-unknown-haiku",
+            "x86_64-pc-windows-msvc",
+            "aarch64-apple-darwin",
+            "x86_64-pc-windows-gnu",
+            "thumbv6m-none-eabi",
+        ];
+        let groups: Vec<_> = group_by_family(targets.iter().map(|t| t.to_string()), os_family)
+            .into_iter()
+            .map(|(name, targets)| match name {
+                GroupName::Family(family) => (family, targets),
+                GroupName::Tier(tier) => panic!("Unexpected tier {:?}", tier),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("Apple", vec!["aarch64-apple-darwin".to_string()]),
+                ("Bare metal", vec!["thumbv6m-none-eabi".to_string()]),
+                (
+                    "Windows",
+                    vec![
+                        "x86_64-pc-windows-msvc".to_string(),
+                        "x86_64-pc-windows-gnu".to_string()
+                    ]
+                ),
+                ("Other", vec!["x86_64-unknown-haiku".to_string()]),
+            ],
+            groups
+        );
+    }
+
+    #[test]
+    fn find() {
+        let tiers: HashMap<_, _> = vec![
+            (Tier::Tier1, vec!["x86_64-unknown-linux-gnu".to_string()]),
+            (Tier::Tier3, vec!["x86_64-unknown-haiku".to_string()]),
+        ]
+        .into_iter()
+        .collect();
+        let target_info: HashMap<_, _> = vec![(
+            "x86_64-unknown-linux-gnu".to_string(),
+            TargetInfo {
+                std: Some(StdSupport::Full),
+                host_tools: true,
+                notes: "64-bit Linux".to_string(),
+            },
+        )]
+        .into_iter()
+        .collect();
+        let targets: HashSet<_> = vec!["x86_64-unknown-linux-gnu", "wasm32-unknown-unknown"]
+            .into_iter()
+            .collect();
+
+        for navigation in [Navigation::Tiers, Navigation::Os].iter().copied() {
+            let table = TiersTable::new(tiers.clone(), &target_info, &targets, navigation);
+
+            let found = table.find("x86_64-unknown-linux-gnu").unwrap();
+            assert_eq!(Tier::Tier1, found.tier);
+            assert_eq!("x86_64-unknown-linux-gnu", found.target.name);
+            assert!(found.target.rendered);
+            assert!(found.target.host_tools);
+            assert_eq!(Some(StdSupport::Full), found.target.std);
+            assert_eq!("64-bit Linux", found.target.notes);
+
+            // Targets without details get the defaults.
+            let found = table.find("x86_64-unknown-haiku").unwrap();
+            assert_eq!(Tier::Tier3, found.tier);
+            assert!(!found.target.rendered);
+            assert!(!found.target.host_tools);
+            assert_eq!(None, found.target.std);
+            assert!(found.target.notes.is_empty());
+
+            // Unlisted targets have no tier, even when they are grouped by a family.
+            assert!(table.find("wasm32-unknown-unknown").is_none());
+            assert!(table.find("aarch64-apple-darwin").is_none());
         }
     }
 }